@@ -0,0 +1,523 @@
+//! 前台窗口与输入空闲获取后端：Windows (Win32)、Linux X11、Wayland (sway / GNOME)
+
+/// 前台窗口获取后端，运行时由 `detect` 选择
+pub enum Backend {
+    #[cfg(windows)]
+    Windows,
+    #[cfg(target_os = "linux")]
+    X11(Box<linux::X11Backend>),
+    #[cfg(target_os = "linux")]
+    Sway(Option<linux::WaylandIdle>),
+    #[cfg(target_os = "linux")]
+    Gnome,
+    Unsupported,
+}
+
+impl Backend {
+    /// 根据当前平台和会话环境选择后端，idle_threshold_secs 用于注册 Wayland 空闲通知
+    pub fn detect(idle_threshold_secs: u64) -> Self {
+        #[cfg(windows)]
+        {
+            let _ = idle_threshold_secs;
+            Backend::Windows
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let env_set = |key: &str| std::env::var(key).map(|v| !v.is_empty()).unwrap_or(false);
+
+            if env_set("WAYLAND_DISPLAY") {
+                if env_set("SWAYSOCK") {
+                    return Backend::Sway(linux::WaylandIdle::spawn(idle_threshold_secs));
+                }
+                let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+                if desktop.to_uppercase().contains("GNOME") {
+                    return Backend::Gnome;
+                }
+            }
+            // 纯 X11 会话，或 Wayland 下退回 XWayland（只能看到 X 客户端窗口）
+            if env_set("DISPLAY") {
+                if let Some(x11) = linux::X11Backend::connect() {
+                    return Backend::X11(Box::new(x11));
+                }
+            }
+            Backend::Unsupported
+        }
+
+        #[cfg(not(any(windows, target_os = "linux")))]
+        {
+            let _ = idle_threshold_secs;
+            Backend::Unsupported
+        }
+    }
+
+    /// 获取当前前台窗口的标题和进程名，获取失败时返回空字符串
+    pub fn active_window(&mut self) -> (String, String) {
+        match self {
+            #[cfg(windows)]
+            Backend::Windows => win::active_window(),
+            #[cfg(target_os = "linux")]
+            Backend::X11(x11) => x11.active_window(),
+            #[cfg(target_os = "linux")]
+            Backend::Sway(_) => linux::sway_active_window(),
+            #[cfg(target_os = "linux")]
+            Backend::Gnome => linux::gnome_active_window(),
+            Backend::Unsupported => (String::new(), String::new()),
+        }
+    }
+
+    /// 空闲阈值变化时调用：sway 的空闲通知按阈值注册，需要重新注册
+    pub fn set_idle_threshold(&mut self, idle_threshold_secs: u64) {
+        #[cfg(target_os = "linux")]
+        if let Backend::Sway(Some(idle)) = self {
+            idle.set_threshold(idle_threshold_secs);
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = idle_threshold_secs;
+    }
+
+    /// 距最后一次键盘/鼠标输入的秒数，后端不支持时返回 None
+    pub fn idle_secs(&mut self) -> Option<u64> {
+        match self {
+            #[cfg(windows)]
+            Backend::Windows => win::idle_secs(),
+            #[cfg(target_os = "linux")]
+            Backend::X11(x11) => x11.idle_secs(),
+            #[cfg(target_os = "linux")]
+            Backend::Sway(idle) => idle.as_ref().map(|i| i.idle_secs()),
+            #[cfg(target_os = "linux")]
+            Backend::Gnome => linux::mutter_idle_secs(),
+            Backend::Unsupported => None,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod win {
+    use windows::Win32::System::Threading::OpenProcess;
+    use windows::Win32::System::Threading::QueryFullProcessImageNameW;
+    use windows::Win32::System::Threading::PROCESS_NAME_FORMAT;
+    use windows::Win32::System::Threading::PROCESS_QUERY_LIMITED_INFORMATION;
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+    use windows::Win32::UI::WindowsAndMessaging::GetWindowTextW;
+    use windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId;
+
+    /// 获取当前前台窗口的标题和进程名
+    pub fn active_window() -> (String, String) {
+        unsafe {
+            let hwnd = GetForegroundWindow();
+            if hwnd.is_invalid() {
+                return (String::new(), String::new());
+            }
+
+            // 获取窗口标题
+            let mut title_buf = [0u16; 512];
+            let len = GetWindowTextW(hwnd, &mut title_buf);
+            let title = String::from_utf16_lossy(&title_buf[..len as usize]);
+
+            // 获取进程 ID
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, Some(&mut pid));
+            if pid == 0 {
+                return (title, String::new());
+            }
+
+            // 获取进程名
+            let exe = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
+                Ok(handle) => {
+                    let mut name_buf = [0u16; 512];
+                    let mut size = name_buf.len() as u32;
+                    let ok = QueryFullProcessImageNameW(
+                        handle,
+                        PROCESS_NAME_FORMAT(0),
+                        windows::core::PWSTR(name_buf.as_mut_ptr()),
+                        &mut size,
+                    );
+                    let _ = windows::Win32::Foundation::CloseHandle(handle);
+                    if ok.is_ok() {
+                        let full = String::from_utf16_lossy(&name_buf[..size as usize]);
+                        full.rsplit('\\').next().unwrap_or("").to_string()
+                    } else {
+                        String::new()
+                    }
+                }
+                Err(_) => String::new(),
+            };
+
+            (title, exe)
+        }
+    }
+
+    /// GetLastInputInfo：距最后一次输入的秒数
+    pub fn idle_secs() -> Option<u64> {
+        use windows::Win32::System::SystemInformation::GetTickCount;
+        use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+        unsafe {
+            let mut info = LASTINPUTINFO {
+                cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
+                dwTime: 0,
+            };
+            if !GetLastInputInfo(&mut info).as_bool() {
+                return None;
+            }
+            // tick 计数约 49.7 天回绕一次，用 wrapping_sub
+            let idle_ms = GetTickCount().wrapping_sub(info.dwTime);
+            Some(idle_ms as u64 / 1000)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub mod linux {
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use wayland_client::globals::{registry_queue_init, GlobalListContents};
+    use wayland_client::protocol::{wl_registry, wl_seat};
+    use wayland_client::{Connection as WaylandConnection, Dispatch, QueueHandle};
+    use wayland_protocols::ext::idle_notify::v1::client::{ext_idle_notification_v1, ext_idle_notifier_v1};
+    use x11rb::connection::Connection;
+    use x11rb::protocol::screensaver::ConnectionExt as _;
+    use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt, Window};
+    use x11rb::rust_connection::RustConnection;
+
+    /// 通过 /proc/{pid}/exe 取进程的可执行文件名
+    pub fn exe_name_of_pid(pid: u32) -> Option<String> {
+        if pid == 0 {
+            return None;
+        }
+        if let Ok(path) = std::fs::read_link(format!("/proc/{}/exe", pid)) {
+            if let Some(name) = path.file_name() {
+                return Some(name.to_string_lossy().to_string());
+            }
+        }
+        // 无权限读取 exe 链接时退回 comm
+        std::fs::read_to_string(format!("/proc/{}/comm", pid))
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    pub struct X11Backend {
+        conn: RustConnection,
+        root: Window,
+        net_active_window: Atom,
+        net_wm_name: Atom,
+        net_wm_pid: Atom,
+        utf8_string: Atom,
+    }
+
+    impl X11Backend {
+        pub fn connect() -> Option<Self> {
+            let (conn, screen_num) = x11rb::connect(None).ok()?;
+            let root = conn.setup().roots.get(screen_num)?.root;
+            let intern = |name: &[u8]| -> Option<Atom> {
+                Some(conn.intern_atom(false, name).ok()?.reply().ok()?.atom)
+            };
+            let net_active_window = intern(b"_NET_ACTIVE_WINDOW")?;
+            let net_wm_name = intern(b"_NET_WM_NAME")?;
+            let net_wm_pid = intern(b"_NET_WM_PID")?;
+            let utf8_string = intern(b"UTF8_STRING")?;
+            Some(Self { conn, root, net_active_window, net_wm_name, net_wm_pid, utf8_string })
+        }
+
+        fn property(&self, window: Window, prop: Atom, ty: Atom) -> Option<x11rb::protocol::xproto::GetPropertyReply> {
+            self.conn
+                .get_property(false, window, prop, ty, 0, 1024)
+                .ok()?
+                .reply()
+                .ok()
+        }
+
+        fn active_window_id(&self) -> Option<Window> {
+            let reply = self.property(self.root, self.net_active_window, AtomEnum::WINDOW.into())?;
+            let window = reply.value32()?.next()?;
+            if window == 0 { None } else { Some(window) }
+        }
+
+        fn title(&self, window: Window) -> String {
+            if let Some(reply) = self.property(window, self.net_wm_name, self.utf8_string) {
+                if !reply.value.is_empty() {
+                    return String::from_utf8_lossy(&reply.value).to_string();
+                }
+            }
+            self.property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into())
+                .map(|r| String::from_utf8_lossy(&r.value).to_string())
+                .unwrap_or_default()
+        }
+
+        fn exe(&self, window: Window) -> String {
+            let pid = self
+                .property(window, self.net_wm_pid, AtomEnum::CARDINAL.into())
+                .and_then(|r| r.value32().and_then(|mut v| v.next()));
+            if let Some(name) = pid.and_then(exe_name_of_pid) {
+                return name;
+            }
+            // 没有 _NET_WM_PID 时用 WM_CLASS 的 class 部分（instance\0class\0）
+            self.property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into())
+                .map(|r| {
+                    r.value
+                        .split(|b| *b == 0)
+                        .rfind(|s| !s.is_empty())
+                        .map(|s| String::from_utf8_lossy(s).to_string())
+                        .unwrap_or_default()
+                })
+                .unwrap_or_default()
+        }
+
+        pub fn active_window(&mut self) -> (String, String) {
+            match self.active_window_id() {
+                Some(window) => (self.title(window), self.exe(window)),
+                None => (String::new(), String::new()),
+            }
+        }
+
+        /// XScreenSaver 扩展：距最后一次输入的秒数
+        pub fn idle_secs(&mut self) -> Option<u64> {
+            let reply = self.conn.screensaver_query_info(self.root).ok()?.reply().ok()?;
+            Some(reply.ms_since_user_input as u64 / 1000)
+        }
+    }
+
+    /// GNOME (Wayland)：Mutter IdleMonitor 的 GetIdletime，输出形如 (uint64 12345,)
+    pub fn mutter_idle_secs() -> Option<u64> {
+        let output = Command::new("gdbus")
+            .args([
+                "call",
+                "--session",
+                "--dest",
+                "org.gnome.Mutter.IdleMonitor",
+                "--object-path",
+                "/org/gnome/Mutter/IdleMonitor/Core",
+                "--method",
+                "org.gnome.Mutter.IdleMonitor.GetIdletime",
+            ])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        let ms: u64 = text
+            .trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .trim_end_matches(',')
+            .trim_start_matches("uint64")
+            .trim()
+            .parse()
+            .ok()?;
+        Some(ms / 1000)
+    }
+
+    /// wlroots 合成器：ext-idle-notify-v1 空闲通知，在后台线程里派发事件
+    pub struct WaylandIdle {
+        conn: WaylandConnection,
+        qh: QueueHandle<IdleDispatch>,
+        seat: wl_seat::WlSeat,
+        notifier: ext_idle_notifier_v1::ExtIdleNotifierV1,
+        notification: Option<ext_idle_notification_v1::ExtIdleNotificationV1>,
+        threshold_secs: u64,
+        idle_since: Arc<Mutex<Option<Instant>>>,
+    }
+
+    struct IdleDispatch {
+        idle_since: Arc<Mutex<Option<Instant>>>,
+    }
+
+    impl WaylandIdle {
+        /// 连接合成器并按 threshold_secs 注册空闲通知，阈值为 0 时先不注册
+        pub fn spawn(threshold_secs: u64) -> Option<Self> {
+            let conn = WaylandConnection::connect_to_env().ok()?;
+            let (globals, mut queue) = registry_queue_init::<IdleDispatch>(&conn).ok()?;
+            let qh = queue.handle();
+            let seat: wl_seat::WlSeat = globals.bind(&qh, 1..=1, ()).ok()?;
+            let notifier: ext_idle_notifier_v1::ExtIdleNotifierV1 = globals.bind(&qh, 1..=1, ()).ok()?;
+
+            let idle_since = Arc::new(Mutex::new(None));
+            let mut state = IdleDispatch { idle_since: idle_since.clone() };
+            std::thread::spawn(move || while queue.blocking_dispatch(&mut state).is_ok() {});
+
+            let mut idle = Self { conn, qh, seat, notifier, notification: None, threshold_secs: 0, idle_since };
+            idle.set_threshold(threshold_secs);
+            Some(idle)
+        }
+
+        /// 阈值变化时销毁旧通知并按新阈值重新注册，0 表示不再注册
+        pub fn set_threshold(&mut self, threshold_secs: u64) {
+            if threshold_secs == self.threshold_secs {
+                return;
+            }
+            if let Some(old) = self.notification.take() {
+                old.destroy();
+            }
+            if let Ok(mut since) = self.idle_since.lock() {
+                *since = None;
+            }
+            if threshold_secs > 0 {
+                let timeout_ms = threshold_secs.saturating_mul(1000).min(u32::MAX as u64) as u32;
+                let threshold = Duration::from_secs(threshold_secs);
+                self.notification = Some(self.notifier.get_idle_notification(timeout_ms, &self.seat, &self.qh, threshold));
+            }
+            self.threshold_secs = threshold_secs;
+            let _ = self.conn.flush();
+        }
+
+        /// 未达到阈值时返回 0，空闲后返回从最后一次输入算起的秒数
+        pub fn idle_secs(&self) -> u64 {
+            self.idle_since
+                .lock()
+                .ok()
+                .and_then(|s| *s)
+                .map(|since| since.elapsed().as_secs())
+                .unwrap_or(0)
+        }
+    }
+
+    impl Drop for WaylandIdle {
+        fn drop(&mut self) {
+            if let Some(notification) = self.notification.take() {
+                notification.destroy();
+            }
+            let _ = self.conn.flush();
+        }
+    }
+
+    /// 通知的用户数据是注册时的阈值，事件到达时据此回溯
+    impl Dispatch<ext_idle_notification_v1::ExtIdleNotificationV1, Duration> for IdleDispatch {
+        fn event(
+            state: &mut Self,
+            _: &ext_idle_notification_v1::ExtIdleNotificationV1,
+            event: ext_idle_notification_v1::Event,
+            threshold: &Duration,
+            _: &WaylandConnection,
+            _: &QueueHandle<Self>,
+        ) {
+            let mut since = match state.idle_since.lock() {
+                Ok(s) => s,
+                Err(_) => return,
+            };
+            match event {
+                // 通知在空闲达到阈值时触发，回溯到最后一次输入的时间点
+                ext_idle_notification_v1::Event::Idled => {
+                    let now = Instant::now();
+                    *since = Some(now.checked_sub(*threshold).unwrap_or(now));
+                }
+                ext_idle_notification_v1::Event::Resumed => *since = None,
+                _ => {}
+            }
+        }
+    }
+
+    impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for IdleDispatch {
+        fn event(_: &mut Self, _: &wl_registry::WlRegistry, _: wl_registry::Event, _: &GlobalListContents, _: &WaylandConnection, _: &QueueHandle<Self>) {}
+    }
+
+    impl Dispatch<wl_seat::WlSeat, ()> for IdleDispatch {
+        fn event(_: &mut Self, _: &wl_seat::WlSeat, _: wl_seat::Event, _: &(), _: &WaylandConnection, _: &QueueHandle<Self>) {}
+    }
+
+    impl Dispatch<ext_idle_notifier_v1::ExtIdleNotifierV1, ()> for IdleDispatch {
+        fn event(_: &mut Self, _: &ext_idle_notifier_v1::ExtIdleNotifierV1, _: ext_idle_notifier_v1::Event, _: &(), _: &WaylandConnection, _: &QueueHandle<Self>) {}
+    }
+
+    /// sway / 兼容 sway IPC 的 wlroots 合成器：通过 swaymsg 取焦点节点
+    pub fn sway_active_window() -> (String, String) {
+        let output = match Command::new("swaymsg").args(["-t", "get_tree", "-r"]).output() {
+            Ok(o) if o.status.success() => o.stdout,
+            _ => return (String::new(), String::new()),
+        };
+        let tree: serde_json::Value = match serde_json::from_slice(&output) {
+            Ok(v) => v,
+            Err(_) => return (String::new(), String::new()),
+        };
+        match find_focused(&tree) {
+            Some(node) => {
+                let title = node.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                let pid = node.get("pid").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+                let exe = exe_name_of_pid(pid)
+                    .or_else(|| node.get("app_id").and_then(|v| v.as_str()).map(|s| s.to_string()))
+                    .or_else(|| {
+                        node.pointer("/window_properties/class")
+                            .and_then(|v| v.as_str())
+                            .map(|s| s.to_string())
+                    })
+                    .unwrap_or_default();
+                (title, exe)
+            }
+            None => (String::new(), String::new()),
+        }
+    }
+
+    fn find_focused(node: &serde_json::Value) -> Option<&serde_json::Value> {
+        if node.get("focused").and_then(|v| v.as_bool()) == Some(true)
+            && node.get("pid").is_some()
+        {
+            return Some(node);
+        }
+        for key in ["nodes", "floating_nodes"] {
+            if let Some(children) = node.get(key).and_then(|v| v.as_array()) {
+                for child in children {
+                    if let Some(found) = find_focused(child) {
+                        return Some(found);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// GNOME (Wayland)：依赖 "Window Calls" 扩展提供的 D-Bus 接口
+    pub fn gnome_active_window() -> (String, String) {
+        let list = match gnome_call("List", &[]) {
+            Some(s) => s,
+            None => return (String::new(), String::new()),
+        };
+        let windows: Vec<serde_json::Value> = serde_json::from_str(&list).unwrap_or_default();
+        let focused = match windows.iter().find(|w| w.get("focus").and_then(|v| v.as_bool()) == Some(true)) {
+            Some(w) => w,
+            None => return (String::new(), String::new()),
+        };
+
+        let title = match focused.get("title").and_then(|v| v.as_str()) {
+            Some(t) => t.to_string(),
+            None => focused
+                .get("id")
+                .and_then(|v| v.as_u64())
+                .and_then(|id| gnome_call("GetTitle", &[id.to_string()]))
+                .unwrap_or_default(),
+        };
+        let pid = focused.get("pid").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        let exe = exe_name_of_pid(pid)
+            .or_else(|| focused.get("wm_class").and_then(|v| v.as_str()).map(|s| s.to_string()))
+            .unwrap_or_default();
+        (title, exe)
+    }
+
+    /// 调用 org.gnome.Shell.Extensions.Windows 的方法，返回解包后的字符串结果
+    fn gnome_call(method: &str, args: &[String]) -> Option<String> {
+        let output = Command::new("gdbus")
+            .args([
+                "call",
+                "--session",
+                "--dest",
+                "org.gnome.Shell",
+                "--object-path",
+                "/org/gnome/Shell/Extensions/Windows",
+                "--method",
+            ])
+            .arg(format!("org.gnome.Shell.Extensions.Windows.{}", method))
+            .args(args)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        // gdbus 输出形如 ('...',)
+        let text = String::from_utf8_lossy(&output.stdout);
+        let inner = text.trim().strip_prefix("('")?.strip_suffix("',)")?;
+        Some(inner.replace("\\'", "'").replace("\\\\", "\\"))
+    }
+}
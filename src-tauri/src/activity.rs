@@ -1,42 +1,30 @@
-use crate::tracker::ActivityEntry;
+use crate::categories::{self, RuleSet};
+use crate::edits;
+use crate::rollup;
+use crate::tracker::{self, ActivityEntry};
+use chrono::{Datelike, Local, NaiveDate};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
-/// 分类规则
-const CATEGORY_RULES: &[(&str, &[&str])] = &[
-    ("编程", &[
-        "code", "visual studio", "pycharm", "idea", "webstorm", "vim",
-        "neovim", "sublime", "atom", "cursor", "windsurf", "devtools",
-        "terminal", "powershell", "cmd.exe", "windowsterminal",
-        "git", "node", "python", "java", "claude",
-    ]),
-    ("浏览器", &["chrome", "firefox", "edge", "msedge", "brave", "opera", "safari"]),
-    ("游戏", &[
-        "steam", "epicgames", "genshinimpact", "yuanshen", "game",
-        "minecraft", "league", "valorant", "原神", "崩坏", "mhy",
-    ]),
-    ("视频", &["youtube", "bilibili", "vlc", "mpv", "potplayer", "哔哩哔哩"]),
-    ("社交", &["wechat", "weixin", "telegram", "discord", "qq", "slack", "teams"]),
-    ("文档", &[
-        "word", "excel", "powerpoint", "wps", "notion", "obsidian",
-        "typora", "notepad", "onenote",
-    ]),
-    ("音乐", &["spotify", "netease", "cloudmusic", "qqmusic", "网易云"]),
-];
-
-fn categorize(exe: &str, title: &str) -> &'static str {
-    let text = format!("{} {}", exe, title).to_lowercase();
-    for (cat, keywords) in CATEGORY_RULES {
-        if keywords.iter().any(|kw| text.contains(kw)) {
-            return cat;
-        }
+/// 记录的分类：优先用写入时存下的分类，旧记录按当前规则现算
+pub fn category_of(rules: &RuleSet, e: &ActivityEntry) -> String {
+    match &e.category {
+        Some(cat) => cat.clone(),
+        None => rules.categorize_entry(e),
     }
-    "其他"
 }
 
-/// 加载指定日期的活动记录
+/// 加载指定日期的活动记录（已应用手动修改）
 pub fn load_entries(data_dir: &PathBuf, date: &str) -> Vec<ActivityEntry> {
+    let mut entries = load_raw_entries(data_dir, date);
+    edits::apply(&mut entries, &edits::load(data_dir, date));
+    entries
+}
+
+/// 加载追踪器写入的原始记录，不含手动修改
+pub fn load_raw_entries(data_dir: &PathBuf, date: &str) -> Vec<ActivityEntry> {
     let path = data_dir.join("activity").join(format!("{}.jsonl", date));
     let mut entries = Vec::new();
     if !path.exists() {
@@ -55,15 +43,45 @@ pub fn load_entries(data_dir: &PathBuf, date: &str) -> Vec<ActivityEntry> {
     entries
 }
 
+/// 读取、修改并整体重写指定日期的原始活动记录
+///
+/// 整个过程持有活动写锁，期间追踪器的追加写会等待；f 返回 false 表示没有改动，不写盘
+pub fn update_entries<F>(data_dir: &PathBuf, date: &str, f: F) -> Result<(), String>
+where
+    F: FnOnce(&mut Vec<ActivityEntry>) -> bool,
+{
+    let _guard = tracker::ACTIVITY_WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = load_raw_entries(data_dir, date);
+    if !f(&mut entries) {
+        return Ok(());
+    }
+    let dir = data_dir.join("activity");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let mut content = String::new();
+    for e in &entries {
+        content.push_str(&serde_json::to_string(e).map_err(|e| e.to_string())?);
+        content.push('\n');
+    }
+    // 先写临时文件再替换，避免写到一半崩溃丢数据
+    let tmp = dir.join(format!("{}.jsonl.tmp", date));
+    fs::write(&tmp, content).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, dir.join(format!("{}.jsonl", date))).map_err(|e| e.to_string())
+}
+
+/// 活动总时长（秒），不含离开时段
+pub fn active_seconds(entries: &[ActivityEntry]) -> u64 {
+    entries.iter().filter(|e| !e.afk).map(|e| e.duration).sum()
+}
+
 /// 按分类汇总活动时长，返回有序的 Vec（按时长降序）
-pub fn summarize(entries: &[ActivityEntry]) -> Vec<(String, u64)> {
+pub fn summarize(data_dir: &PathBuf, entries: &[ActivityEntry]) -> Vec<(String, u64)> {
+    let rules = categories::load(data_dir);
     let mut map: HashMap<String, u64> = HashMap::new();
     for e in entries {
-        if e.duration < 30 {
+        if e.afk || e.duration < 30 {
             continue;
         }
-        let cat = categorize(&e.exe, &e.title);
-        *map.entry(cat.to_string()).or_insert(0) += e.duration;
+        *map.entry(category_of(&rules, e)).or_insert(0) += e.duration;
     }
     let mut sorted: Vec<(String, u64)> = map.into_iter().collect();
     sorted.sort_by(|a, b| b.1.cmp(&a.1));
@@ -72,35 +90,143 @@ pub fn summarize(entries: &[ActivityEntry]) -> Vec<(String, u64)> {
 
 /// 获取最近 N 天每天的总活动时长（秒），返回 [(日期, 秒数)]
 pub fn daily_totals(data_dir: &PathBuf, days: usize) -> Vec<(String, u64)> {
-    use chrono::Local;
-    let today = Local::now().date_naive();
-    let mut result = Vec::new();
-    for i in (0..days).rev() {
-        let date = today - chrono::Duration::days(i as i64);
-        let ds = date.format("%Y-%m-%d").to_string();
-        let entries = load_entries(data_dir, &ds);
-        let total: u64 = entries.iter().map(|e| e.duration).sum();
-        result.push((ds, total));
-    }
-    result
+    let p = ReportPeriod::last_days(days);
+    daily_totals_between(data_dir, p.from, p.to)
 }
 
 /// 获取最近 N 天的分类汇总
 pub fn range_summary(data_dir: &PathBuf, days: usize) -> Vec<(String, u64)> {
-    use chrono::Local;
-    let today = Local::now().date_naive();
-    let mut map: HashMap<String, u64> = HashMap::new();
-    for i in 0..days {
-        let date = today - chrono::Duration::days(i as i64);
-        let ds = date.format("%Y-%m-%d").to_string();
-        let entries = load_entries(data_dir, &ds);
-        for e in &entries {
-            if e.duration < 30 { continue; }
-            let cat = categorize(&e.exe, &e.title);
-            *map.entry(cat.to_string()).or_insert(0) += e.duration;
+    let p = ReportPeriod::last_days(days);
+    range_summary_between(data_dir, p.from, p.to)
+}
+
+/// 获取 [from, to] 内每天的总活动时长（秒）
+pub fn daily_totals_between(data_dir: &PathBuf, from: NaiveDate, to: NaiveDate) -> Vec<(String, u64)> {
+    rollup::range(data_dir, from, to)
+        .into_iter()
+        .map(|d| (d.date, d.active_sec))
+        .collect()
+}
+
+/// 获取 [from, to] 内的分类汇总
+pub fn range_summary_between(data_dir: &PathBuf, from: NaiveDate, to: NaiveDate) -> Vec<(String, u64)> {
+    rollup::merge_categories(&rollup::range(data_dir, from, to))
+}
+
+/// 统计/报告的时间段（闭区间）
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportPeriod {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// 展示用描述，如「最近 7 天」「2026 年第 10 周」
+    pub label: String,
+}
+
+impl ReportPeriod {
+    /// 截至今天的最近 N 天
+    pub fn last_days(days: usize) -> Self {
+        let to = Local::now().date_naive();
+        let from = to - chrono::Duration::days(days as i64 - 1);
+        Self { from, to, label: format!("最近 {} 天", days) }
+    }
+
+    /// 解析时间段：`2026-W10`（ISO 周）、`2026-03`（自然月）或 `2026-03-01..2026-03-15`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if let Some((a, b)) = s.split_once("..") {
+            let (from, to) = (parse_date(a)?, parse_date(b)?);
+            if from > to {
+                return Err("开始日期不能晚于结束日期".into());
+            }
+            return Ok(Self { from, to, label: format!("{} 至 {}", from, to) });
         }
+        if let Some((y, w)) = s.split_once("-W").or_else(|| s.split_once("-w")) {
+            let (year, week) = (y.parse::<i32>(), w.parse::<u32>());
+            if let (Ok(year), Ok(week)) = (year, week) {
+                let from = NaiveDate::from_isoywd_opt(year, week, chrono::Weekday::Mon)
+                    .ok_or_else(|| format!("无效的周: {}", s))?;
+                let to = from + chrono::Duration::days(6);
+                return Ok(Self { from, to, label: format!("{} 年第 {} 周", year, week) });
+            }
+        }
+        if let Some((y, m)) = s.split_once('-') {
+            if let (Ok(year), Ok(month)) = (y.parse::<i32>(), m.parse::<u32>()) {
+                let from = NaiveDate::from_ymd_opt(year, month, 1).ok_or_else(|| format!("无效的月份: {}", s))?;
+                let next = if month == 12 {
+                    NaiveDate::from_ymd_opt(year + 1, 1, 1)
+                } else {
+                    NaiveDate::from_ymd_opt(year, month + 1, 1)
+                };
+                let to = next.map(|d| d - chrono::Duration::days(1)).unwrap_or(from);
+                return Ok(Self { from, to, label: format!("{} 年 {} 月", year, month) });
+            }
+        }
+        Err(format!("无法识别的时间段: {}（支持 2026-W10、2026-03、2026-03-01..2026-03-15）", s))
     }
-    let mut sorted: Vec<(String, u64)> = map.into_iter().collect();
-    sorted.sort_by(|a, b| b.1.cmp(&a.1));
-    sorted
+
+    pub fn days(&self) -> usize {
+        ((self.to - self.from).num_days() + 1).max(0) as usize
+    }
+}
+
+/// 解析 YYYY-MM-DD 日期
+pub fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(|_| format!("日期格式应为 YYYY-MM-DD: {}", s))
+}
+
+/// 活动热力图：rows 为行标签（周一到周日，或日期），matrix[行][小时] 为秒数
+#[derive(Debug, Clone, Serialize)]
+pub struct Heatmap {
+    pub rows: Vec<String>,
+    pub matrix: Vec<Vec<u64>>,
+    pub max: u64,
+    pub total: u64,
+}
+
+const WEEKDAY_LABELS: [&str; 7] = ["周一", "周二", "周三", "周四", "周五", "周六", "周日"];
+
+/// 统计 [from, to] 内按小时的活动分布，可按分类或进程名过滤（二选一）
+///
+/// by_date 为 false 时按星期几叠加成 7×24，为 true 时每天一行。
+/// 每日汇总只分别记了分类和进程的小时分布，同一进程可能分属多个分类，无法取交集，所以两者同时给出时报错
+pub fn heatmap(
+    data_dir: &PathBuf,
+    from: NaiveDate,
+    to: NaiveDate,
+    category: Option<&str>,
+    exe: Option<&str>,
+    by_date: bool,
+) -> Result<Heatmap, String> {
+    if category.is_some() && exe.is_some() {
+        return Err("热力图只能按分类或进程名之一过滤，不能同时指定".into());
+    }
+    let days = rollup::range(data_dir, from, to);
+    let exe = exe.map(|e| e.to_lowercase());
+    let mut rows = Vec::new();
+    let mut matrix: Vec<Vec<u64>> = Vec::new();
+    if !by_date {
+        rows = WEEKDAY_LABELS.iter().map(|s| s.to_string()).collect();
+        matrix = vec![vec![0; 24]; 7];
+    }
+
+    for day in &days {
+        let hours: Vec<u64> = match (category, &exe) {
+            (Some(cat), _) => day.category_hours.get(cat).cloned().unwrap_or_else(|| vec![0; 24]),
+            (None, Some(exe)) => day.exe_hours.get(exe).cloned().unwrap_or_else(|| vec![0; 24]),
+            (None, None) => day.by_hour.clone(),
+        };
+        if by_date {
+            rows.push(day.date.clone());
+            matrix.push(hours);
+        } else if let Ok(date) = NaiveDate::parse_from_str(&day.date, "%Y-%m-%d") {
+            let row = &mut matrix[date.weekday().num_days_from_monday() as usize];
+            for (h, sec) in hours.iter().enumerate().take(24) {
+                row[h] += sec;
+            }
+        }
+    }
+
+    let max = matrix.iter().flatten().copied().max().unwrap_or(0);
+    let total = matrix.iter().flatten().sum();
+    Ok(Heatmap { rows, matrix, max, total })
 }
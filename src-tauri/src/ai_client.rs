@@ -0,0 +1,798 @@
+use crate::ai_error::{AiFailure, ErrorKind};
+use crate::ai_history::{self, HistoryEntry};
+use crate::ai_mock;
+use crate::ai_provider::{AIProvider, Protocol};
+use crate::ai_usage::{self, Usage, UsageRecord};
+use crate::config::{self, AppConfig};
+use crate::prompts::RenderedPrompt;
+use futures_util::future::{AbortHandle, Abortable};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// 使用 AI 的功能，每个功能可以在设置中单独指定引擎
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    /// 日记生成、整理和周报月报
+    Diary,
+    Theater,
+    Vocab,
+    Alchemy,
+    Writing,
+    Dream,
+}
+
+impl Feature {
+    /// feature_providers 中的键
+    pub fn key(self) -> &'static str {
+        match self {
+            Feature::Diary => "diary",
+            Feature::Theater => "theater",
+            Feature::Vocab => "vocab",
+            Feature::Alchemy => "alchemy",
+            Feature::Writing => "writing",
+            Feature::Dream => "dream",
+        }
+    }
+
+    pub const ALL: [Feature; 6] =
+        [Feature::Diary, Feature::Theater, Feature::Vocab, Feature::Alchemy, Feature::Writing, Feature::Dream];
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.key() == key)
+    }
+}
+
+/// 功能对应的引擎：feature_providers 中指定的，其次 active_provider；指定的引擎不存在或已停用时报错，
+/// 两者都没设置时才用第一个启用的引擎
+pub fn provider_for(cfg: &AppConfig, feature: Feature) -> Result<AIProvider, String> {
+    let non_empty = |id: &&String| !id.is_empty();
+    let (chosen, source) = match cfg.feature_providers.get(feature.key()).filter(non_empty) {
+        Some(id) => (Some(id), format!("功能 {} 指定的引擎", feature.key())),
+        None => (Some(&cfg.active_provider).filter(non_empty), "默认引擎".to_string()),
+    };
+    let Some(id) = chosen else {
+        return cfg
+            .ai_providers
+            .iter()
+            .find(|p| p.enabled)
+            .cloned()
+            .ok_or_else(|| "请先在设置中配置 AI 接口".to_string());
+    };
+    let provider = cfg
+        .ai_providers
+        .iter()
+        .find(|p| &p.id == id)
+        .ok_or_else(|| format!("{} {} 已不存在，请在设置中重新选择", source, id))?;
+    if !provider.enabled {
+        return Err(format!("{} {} 已停用，请在设置中启用或重新选择", source, provider.name));
+    }
+    Ok(provider.clone())
+}
+
+/// 进行中的请求，取消时中止对应的流
+static RUNNING: Mutex<Option<HashMap<String, AbortHandle>>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// ai-start 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct AiStart {
+    pub id: String,
+    pub feature: Feature,
+}
+
+/// ai-chunk 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct AiChunk {
+    pub id: String,
+    pub text: String,
+}
+
+/// ai-done 事件负载；incomplete 表示流中途断开或被拦截，返回的只是已收到的部分，kind/error 说明原因；
+/// cached 表示直接用了历史中相同提示词的结果；warning 为写入用量或生成历史失败的原因，不影响结果
+#[derive(Debug, Clone, Serialize)]
+pub struct AiDone {
+    pub id: String,
+    pub incomplete: bool,
+    pub cached: bool,
+    pub kind: Option<ErrorKind>,
+    pub error: Option<String>,
+    pub warning: Option<String>,
+}
+
+/// ai-error 事件负载，kind 为错误类别，cancelled 表示被用户取消；warning 同 ai-done
+#[derive(Debug, Clone, Serialize)]
+pub struct AiError {
+    pub id: String,
+    pub kind: ErrorKind,
+    pub error: String,
+    pub cancelled: bool,
+    pub warning: Option<String>,
+}
+
+/// ai-retry 事件负载：第 attempt 次重试前等待 wait_secs 秒，kind/error 为上一次失败的原因
+#[derive(Debug, Clone, Serialize)]
+pub struct AiRetry {
+    pub id: String,
+    pub attempt: u32,
+    pub wait_secs: u64,
+    pub kind: ErrorKind,
+    pub error: String,
+}
+
+/// 一次对话的结果；incomplete 不为空时 text 只是断开前收到的部分
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    pub incomplete: Option<AiFailure>,
+    pub usage: Usage,
+    /// 接口没有返回用量，usage 是估算值
+    pub estimated: bool,
+    /// 来自历史缓存，没有调用接口
+    pub cached: bool,
+}
+
+/// 前端没传请求 id 时生成一个
+fn new_request_id() -> String {
+    let ts = chrono::Local::now().timestamp_millis();
+    format!("ai-{}-{}", ts, NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+/// 取消进行中的请求，返回是否找到
+pub fn cancel(id: &str) -> bool {
+    let mut running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+    match running.as_mut().and_then(|m| m.remove(id)) {
+        Some(handle) => {
+            handle.abort();
+            true
+        }
+        None => false,
+    }
+}
+
+/// 用功能对应的引擎流式对话
+///
+/// 事件都带请求 id：开始时 ai-start，每段文本 ai-chunk，等待重试时 ai-retry，成功 ai-done，失败或取消 ai-error。
+/// request_id 由前端生成以便先订阅再调用，缺省时自动生成（可从 ai-start 得知），也是生成历史中的记录 id。
+/// 开启缓存时，相同引擎和提示词在有效期内的完整结果直接返回，不调用接口
+pub async fn chat(app: &AppHandle, feature: Feature, request_id: Option<String>, system: &str, user_msg: &str) -> Result<String, String> {
+    chat_with_warning(app, feature, request_id, system, user_msg, None).await
+}
+
+/// 用渲染好的模板对话；模板退回内置版本的原因随 ai-done / ai-error 的 warning 一起报告
+pub async fn chat_prompt(app: &AppHandle, feature: Feature, request_id: Option<String>, prompt: &RenderedPrompt) -> Result<String, String> {
+    chat_with_warning(app, feature, request_id, &prompt.system, &prompt.user, prompt.warning.clone()).await
+}
+
+async fn chat_with_warning(
+    app: &AppHandle,
+    feature: Feature,
+    request_id: Option<String>,
+    system: &str,
+    user_msg: &str,
+    warning: Option<String>,
+) -> Result<String, String> {
+    let id = request_id.filter(|id| !id.is_empty()).unwrap_or_else(new_request_id);
+    let (handle, registration) = AbortHandle::new_pair();
+    RUNNING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(id.clone(), handle);
+    let _ = app.emit("ai-start", AiStart { id: id.clone(), feature });
+
+    let cfg = config::load_config();
+    let data_dir = PathBuf::from(&cfg.data_dir);
+    // 通过预算检查、请求发出后才有，失败或取消时用它按已收到的内容补记用量
+    let metered: OnceLock<Meter> = OnceLock::new();
+    let warnings: Mutex<Vec<String>> = Mutex::new(warning.into_iter().collect());
+    let warn = |msg: String| warnings.lock().unwrap_or_else(|e| e.into_inner()).push(msg);
+    let run = async {
+        let provider = provider_for(&cfg, feature)?;
+        let hash = ai_history::prompt_hash(system, user_msg);
+        if cfg.ai_cache_hours > 0 {
+            if let Some(hit) = ai_history::cached(&data_dir, feature, &provider.id, &provider.model, &hash, cfg.ai_cache_hours) {
+                let _ = app.emit("ai-chunk", AiChunk { id: id.clone(), text: hit.text.clone() });
+                return Ok(Completion { text: hit.text, incomplete: None, usage: Usage::default(), estimated: false, cached: true });
+            }
+        }
+        let meter = Meter::start(&data_dir, &provider, feature, system, user_msg)?;
+        let meter = metered.get_or_init(|| meter);
+        let done = stream_chat(
+            &provider,
+            feature,
+            system,
+            user_msg,
+            |chunk| {
+                meter.received(chunk);
+                let _ = app.emit("ai-chunk", AiChunk { id: id.clone(), text: chunk.to_string() });
+            },
+            |attempt, wait, failure| {
+                let _ = app.emit("ai-retry", AiRetry {
+                    id: id.clone(),
+                    attempt,
+                    wait_secs: wait.as_secs(),
+                    kind: failure.kind,
+                    error: failure.to_string(),
+                });
+            },
+        )
+        .await?;
+        let (rec, recorded) = meter.finish(&done);
+        if let Err(e) = recorded {
+            warn(e);
+        }
+        let entry = HistoryEntry {
+            id: id.clone(),
+            ts: rec.ts,
+            feature,
+            prompt_hash: hash,
+            provider: rec.provider,
+            model: rec.model,
+            system: system.to_string(),
+            user: user_msg.to_string(),
+            text: done.text.clone(),
+            incomplete: rec.incomplete,
+        };
+        if let Err(e) = ai_history::record(&data_dir, &entry) {
+            warn(format!("记录 AI 生成历史失败: {}", e));
+        }
+        Ok(done)
+    };
+    // 中止时 future 被丢弃，reqwest 连接随之关闭
+    let result = Abortable::new(run, registration)
+        .await
+        .unwrap_or_else(|_| Err(AiFailure::new(ErrorKind::Cancelled, "")));
+
+    // 请求发出后失败或被取消：同样计入用量
+    if let (Err(_), Some(meter)) = (&result, metered.get()) {
+        if let Err(e) = meter.fail() {
+            warn(e);
+        }
+    }
+    let warnings = warnings.into_inner().unwrap_or_else(|e| e.into_inner());
+    let warning = if warnings.is_empty() { None } else { Some(warnings.join("；")) };
+
+    if let Some(m) = RUNNING.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        m.remove(&id);
+    }
+    match result {
+        Ok(done) => {
+            let incomplete = done.incomplete.as_ref();
+            let _ = app.emit("ai-done", AiDone {
+                id,
+                incomplete: incomplete.is_some(),
+                cached: done.cached,
+                kind: incomplete.map(|f| f.kind),
+                error: incomplete.map(|f| f.to_string()),
+                warning,
+            });
+            Ok(done.text)
+        }
+        Err(failure) => {
+            let _ = app.emit("ai-error", AiError {
+                id,
+                kind: failure.kind,
+                error: failure.to_string(),
+                cancelled: failure.kind == ErrorKind::Cancelled,
+                warning,
+            });
+            Err(failure.to_string())
+        }
+    }
+}
+
+/// 按引擎协议流式对话，每段文本回调 on_chunk
+///
+/// 还没收到内容时遇到限流、网络或服务端错误会按指数退避重试（优先遵守 Retry-After），等待前回调 on_retry(第几次重试, 等待时长, 失败原因)；
+/// 已经输出过内容后断开则不再重试，把已收到的部分标记为不完整返回。
+/// 不依赖 Tauri，可在命令行或测试中直接调用；配合模拟引擎可以完全离线运行
+pub async fn stream_chat<F, R>(
+    provider: &AIProvider,
+    feature: Feature,
+    system: &str,
+    user_msg: &str,
+    mut on_chunk: F,
+    mut on_retry: R,
+) -> Result<Completion, AiFailure>
+where
+    F: FnMut(&str),
+    R: FnMut(u32, Duration, &AiFailure),
+{
+    let client = provider.client()?;
+    let mut attempt = 0;
+    loop {
+        let mut received = String::new();
+        let mut usage = None;
+        let failure = match stream_once(&client, provider, feature, system, user_msg, &mut received, &mut usage, &mut on_chunk).await {
+            Ok(()) if received.trim().is_empty() => AiFailure::new(ErrorKind::Other, "API 返回空内容"),
+            Ok(()) => return Ok(completion(system, user_msg, received, usage, None)),
+            Err(failure) => failure,
+        };
+        if !received.trim().is_empty() {
+            return Ok(completion(system, user_msg, received, usage, Some(failure)));
+        }
+        if failure.kind.retryable() && attempt < provider.max_retries {
+            if let Some(wait) = failure.backoff(attempt) {
+                attempt += 1;
+                on_retry(attempt, wait, &failure);
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+        }
+        return Err(failure);
+    }
+}
+
+/// 组装结果，接口没有返回用量时按字数估算
+fn completion(system: &str, user_msg: &str, text: String, usage: Option<Usage>, incomplete: Option<AiFailure>) -> Completion {
+    let estimated = usage.is_none();
+    let usage = usage.unwrap_or_else(|| estimate_usage(system, user_msg, &text));
+    Completion { text: text.trim().to_string(), incomplete, usage, estimated, cached: false }
+}
+
+/// 按字数估算一次调用的用量
+fn estimate_usage(system: &str, user_msg: &str, text: &str) -> Usage {
+    Usage {
+        prompt_tokens: ai_usage::estimate_tokens(system) + ai_usage::estimate_tokens(user_msg),
+        completion_tokens: ai_usage::estimate_tokens(text),
+    }
+}
+
+/// 一次计费调用：开始前检查本月预算，结束时把用量写入 ai_usage；不依赖 Tauri，界面和命令行共用
+pub struct Meter {
+    data_dir: PathBuf,
+    provider: AIProvider,
+    feature: Feature,
+    prompt_tokens: u64,
+    started: Instant,
+    /// 已输出的文本，失败或取消时据此估算输出 token
+    received: Mutex<String>,
+}
+
+impl Meter {
+    /// 超出本月预算时返回 quota 错误，不发请求
+    pub fn start(data_dir: &PathBuf, provider: &AIProvider, feature: Feature, system: &str, user_msg: &str) -> Result<Self, AiFailure> {
+        check_budget(data_dir, provider)?;
+        Ok(Self {
+            data_dir: data_dir.clone(),
+            provider: provider.clone(),
+            feature,
+            prompt_tokens: estimate_usage(system, user_msg, "").prompt_tokens,
+            started: Instant::now(),
+            received: Mutex::new(String::new()),
+        })
+    }
+
+    /// 记下一段输出，传给 stream_chat 的 on_chunk 里调用
+    pub fn received(&self, chunk: &str) {
+        self.received.lock().unwrap_or_else(|e| e.into_inner()).push_str(chunk);
+    }
+
+    /// 拿到结果（含不完整的）：用接口返回的用量，没有时用估算值；返回写入的记录和写入结果
+    pub fn finish(&self, done: &Completion) -> (UsageRecord, Result<(), String>) {
+        let rec = self.record(done.usage, done.estimated, done.incomplete.is_some());
+        let written = ai_usage::record(&self.data_dir, &rec).map_err(|e| format!("记录 AI 用量失败: {}", e));
+        (rec, written)
+    }
+
+    /// 失败或被取消：接口没有返回用量，按已收到的内容估算，同样计入预算
+    pub fn fail(&self) -> Result<(), String> {
+        let text = self.received.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let usage = Usage { prompt_tokens: self.prompt_tokens, completion_tokens: ai_usage::estimate_tokens(&text) };
+        let rec = self.record(usage, true, true);
+        ai_usage::record(&self.data_dir, &rec).map_err(|e| format!("记录 AI 用量失败: {}", e))
+    }
+
+    fn record(&self, usage: Usage, estimated: bool, incomplete: bool) -> UsageRecord {
+        UsageRecord {
+            ts: ai_usage::now_ts(),
+            provider: self.provider.id.clone(),
+            model: self.provider.model.clone(),
+            feature: self.feature.key().to_string(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            estimated,
+            latency_ms: self.started.elapsed().as_millis() as u64,
+            incomplete,
+        }
+    }
+}
+
+/// 超出本月预算时拒绝调用
+fn check_budget(data_dir: &PathBuf, provider: &AIProvider) -> Result<(), AiFailure> {
+    if provider.monthly_budget_tokens == 0 {
+        return Ok(());
+    }
+    let used = ai_usage::month_tokens(data_dir, &provider.id);
+    if used >= provider.monthly_budget_tokens {
+        return Err(AiFailure::new(
+            ErrorKind::Quota,
+            format!("{} 本月已用 {} tokens，超出预算 {}", provider.name, used, provider.monthly_budget_tokens),
+        ));
+    }
+    Ok(())
+}
+
+/// 发一次请求并读完整个流，收到的文本追加到 received，接口返回的用量写入 usage
+#[allow(clippy::too_many_arguments)]
+async fn stream_once<F>(
+    client: &reqwest::Client,
+    provider: &AIProvider,
+    feature: Feature,
+    system: &str,
+    user_msg: &str,
+    received: &mut String,
+    usage: &mut Option<Usage>,
+    on_chunk: &mut F,
+) -> Result<(), AiFailure>
+where
+    F: FnMut(&str),
+{
+    if provider.protocol == Protocol::Mock {
+        let request = provider
+            .chat_request(client, system, user_msg, true)?
+            .build()
+            .map_err(|e| AiFailure::new(ErrorKind::Config, e.to_string()))?;
+        return ai_mock::stream(&provider.mock_settings(), feature, &request, received, on_chunk).await;
+    }
+    let resp = provider
+        .chat_request(client, system, user_msg, true)?
+        .send()
+        .await
+        .map_err(|e| AiFailure::from_reqwest(&e))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AiFailure::from_response(status, &headers, &text));
+    }
+
+    let mut decoder = StreamDecoder::new(provider.protocol);
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| AiFailure::from_reqwest(&e))?;
+        for text in decoder.feed(&chunk)? {
+            received.push_str(&text);
+            on_chunk(&text);
+        }
+        if decoder.is_done() {
+            break;
+        }
+    }
+    for text in decoder.finish()? {
+        received.push_str(&text);
+        on_chunk(&text);
+    }
+    *usage = decoder.usage();
+
+    if decoder.is_filtered() {
+        return Err(AiFailure::new(ErrorKind::ContentFilter, ""));
+    }
+    if !decoder.is_complete() {
+        return Err(AiFailure::new(ErrorKind::Network, "响应流意外结束"));
+    }
+    Ok(())
+}
+
+/// 流式响应解析：按字节缓冲到整行再解码（多字节字符可能被拆在两个网络包里），再按协议取出文本
+///
+/// OpenAI 兼容和 Anthropic 是 SSE 的 data: 行，Ollama 是每行一个 JSON
+pub struct StreamDecoder {
+    protocol: Protocol,
+    pending: Vec<u8>,
+    done: bool,
+    /// 模型已给出结束原因（OpenAI 的 finish_reason 之后还有带用量的分片和 [DONE]）
+    finished: bool,
+    /// 模型因安全策略停止输出
+    filtered: bool,
+    usage: Option<Usage>,
+}
+
+impl StreamDecoder {
+    pub fn new(protocol: Protocol) -> Self {
+        Self { protocol, pending: Vec::new(), done: false, finished: false, filtered: false, usage: None }
+    }
+
+    /// 喂入一段字节，返回其中完整行里的文本；流结束标记之后不再返回，接口报错时返回 Err
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Vec<String>, AiFailure> {
+        self.pending.extend_from_slice(bytes);
+        let mut out = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            self.take_line(&line, &mut out)?;
+        }
+        Ok(out)
+    }
+
+    /// 流结束时处理最后一行（没有换行结尾的情况）
+    pub fn finish(&mut self) -> Result<Vec<String>, AiFailure> {
+        let line = std::mem::take(&mut self.pending);
+        let mut out = Vec::new();
+        self.take_line(&line, &mut out)?;
+        Ok(out)
+    }
+
+    /// 收到了流结束标记（[DONE]、message_stop 或 done: true），之后的数据不再读取
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// 流正常结束：收到结束标记，或模型已给出结束原因（有的兼容接口不发 [DONE]）
+    pub fn is_complete(&self) -> bool {
+        self.done || self.finished
+    }
+
+    /// 接口返回的用量；Anthropic 的输入和输出分别在开头和结尾的事件里
+    pub fn usage(&self) -> Option<Usage> {
+        self.usage
+    }
+
+    pub fn is_filtered(&self) -> bool {
+        self.filtered
+    }
+
+    fn take_line(&mut self, line: &[u8], out: &mut Vec<String>) -> Result<(), AiFailure> {
+        if self.done {
+            return Ok(());
+        }
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        let data = match self.protocol {
+            Protocol::Ollama => line,
+            // 模拟引擎不经过网络，没有流需要解析
+            Protocol::Mock => return Ok(()),
+            Protocol::OpenAI | Protocol::Anthropic => match line.strip_prefix("data:") {
+                Some(data) => data.trim(),
+                None => return Ok(()),
+            },
+        };
+        if data.is_empty() {
+            return Ok(());
+        }
+        if data == "[DONE]" {
+            self.done = true;
+            return Ok(());
+        }
+        // 解析不了的行（心跳、注释等）直接跳过
+        let Ok(v) = serde_json::from_str::<serde_json::Value>(data) else {
+            return Ok(());
+        };
+        if let Some(err) = v.get("error").filter(|e| !e.is_null()) {
+            return Err(AiFailure::from_stream_error(err));
+        }
+        let text = match self.protocol {
+            Protocol::OpenAI => {
+                if let Some(u) = v["usage"].as_object() {
+                    self.usage = Some(Usage {
+                        prompt_tokens: u.get("prompt_tokens").and_then(|t| t.as_u64()).unwrap_or(0),
+                        completion_tokens: u.get("completion_tokens").and_then(|t| t.as_u64()).unwrap_or(0),
+                    });
+                }
+                let choice = &v["choices"][0];
+                if let Some(reason) = choice["finish_reason"].as_str() {
+                    self.finished = true;
+                    self.filtered = reason == "content_filter";
+                }
+                choice["delta"]["content"].as_str()
+            }
+            Protocol::Anthropic => {
+                match v["type"].as_str() {
+                    Some("message_start") => {
+                        let usage = self.usage.get_or_insert_with(Usage::default);
+                        usage.prompt_tokens = v["message"]["usage"]["input_tokens"].as_u64().unwrap_or(0);
+                        return Ok(());
+                    }
+                    Some("message_stop") => self.done = true,
+                    Some("message_delta") => {
+                        self.filtered = v["delta"]["stop_reason"].as_str() == Some("refusal");
+                        if let Some(out) = v["usage"]["output_tokens"].as_u64() {
+                            self.usage.get_or_insert_with(Usage::default).completion_tokens = out;
+                        }
+                    }
+                    Some("content_block_delta") => {}
+                    _ => return Ok(()),
+                }
+                v["delta"]["text"].as_str()
+            }
+            Protocol::Ollama => {
+                if v["done"].as_bool() == Some(true) {
+                    self.done = true;
+                    if let (Some(p), Some(c)) = (v["prompt_eval_count"].as_u64(), v["eval_count"].as_u64()) {
+                        self.usage = Some(Usage { prompt_tokens: p, completion_tokens: c });
+                    }
+                }
+                v["message"]["content"].as_str()
+            }
+            Protocol::Mock => None,
+        };
+        if let Some(text) = text.filter(|t| !t.is_empty()) {
+            out.push(text.to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_mock;
+
+    fn provider(id: &str, enabled: bool) -> AIProvider {
+        AIProvider { id: id.to_string(), name: id.to_string(), enabled, ..ai_mock::provider() }
+    }
+
+    fn config(providers: Vec<AIProvider>, active: &str, diary: Option<&str>) -> AppConfig {
+        let mut cfg = AppConfig { ai_providers: providers, active_provider: active.to_string(), ..AppConfig::default() };
+        if let Some(id) = diary {
+            cfg.feature_providers.insert("diary".to_string(), id.to_string());
+        }
+        cfg
+    }
+
+    #[test]
+    fn provider_for_uses_feature_then_active_provider() {
+        let cfg = config(vec![provider("a", true), provider("b", true)], "b", Some("a"));
+        assert_eq!(provider_for(&cfg, Feature::Diary).unwrap().id, "a");
+        assert_eq!(provider_for(&cfg, Feature::Dream).unwrap().id, "b");
+    }
+
+    #[test]
+    fn provider_for_rejects_missing_or_disabled_choice() {
+        let cfg = config(vec![provider("a", true)], "a", Some("gone"));
+        assert!(provider_for(&cfg, Feature::Diary).unwrap_err().contains("gone"));
+        let cfg = config(vec![provider("a", true)], "gone", None);
+        assert!(provider_for(&cfg, Feature::Diary).unwrap_err().contains("默认引擎"));
+        let cfg = config(vec![provider("a", true), provider("b", false)], "a", Some("b"));
+        assert!(provider_for(&cfg, Feature::Diary).unwrap_err().contains("已停用"));
+    }
+
+    #[test]
+    fn meter_records_failed_calls_against_the_budget() {
+        let dir = std::env::temp_dir().join(format!("xiaolanniao-meter-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut p = provider("paid", true);
+        p.monthly_budget_tokens = 6;
+        let meter = Meter::start(&dir, &p, Feature::Diary, "系统", "消息").unwrap();
+        meter.received("部分输出");
+        meter.fail().unwrap();
+        // 输入 4 + 已输出 4，估算值写入并计入预算
+        assert_eq!(ai_usage::month_tokens(&dir, "paid"), 8);
+        let err = Meter::start(&dir, &p, Feature::Diary, "系统", "消息").err().unwrap();
+        assert_eq!(err.kind, ErrorKind::Quota);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn provider_for_falls_back_only_when_nothing_is_chosen() {
+        let cfg = config(vec![provider("a", false), provider("b", true)], "", None);
+        assert_eq!(provider_for(&cfg, Feature::Diary).unwrap().id, "b");
+        assert!(provider_for(&config(vec![], "", None), Feature::Diary).is_err());
+    }
+
+    /// 逐段喂入，返回拼好的文本和解码器（之后可查看结束状态和用量）
+    fn decode(protocol: Protocol, chunks: &[&[u8]]) -> Result<(String, StreamDecoder), AiFailure> {
+        let mut decoder = StreamDecoder::new(protocol);
+        let mut text = String::new();
+        for chunk in chunks {
+            text.extend(decoder.feed(chunk)?);
+        }
+        text.extend(decoder.finish()?);
+        Ok((text, decoder))
+    }
+
+    fn tokens(decoder: &StreamDecoder) -> Option<(u64, u64)> {
+        decoder.usage().map(|u| (u.prompt_tokens, u.completion_tokens))
+    }
+
+    #[test]
+    fn decodes_anthropic_events_with_usage() {
+        let stream = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":12,\"output_tokens\":1}}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: ping\n",
+            "data: {\"type\":\"ping\"}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"你好\"}}\n\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"，世界\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":7}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"多余\"}}\n",
+        );
+        let (text, decoder) = decode(Protocol::Anthropic, &[stream.as_bytes()]).unwrap();
+        assert_eq!(text, "你好，世界");
+        assert!(decoder.is_done() && decoder.is_complete() && !decoder.is_filtered());
+        assert_eq!(tokens(&decoder), Some((12, 7)));
+
+        let refusal = "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"refusal\"},\"usage\":{\"output_tokens\":0}}\n";
+        let (_, decoder) = decode(Protocol::Anthropic, &[refusal.as_bytes()]).unwrap();
+        assert!(decoder.is_filtered());
+    }
+
+    #[test]
+    fn decodes_ollama_lines_with_done_counts() {
+        let stream = concat!(
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"小\"},\"done\":false}\n",
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"蓝鸟\"},\"done\":false}\n",
+            // 最后一行没有换行结尾，由 finish 处理
+            "{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"prompt_eval_count\":26,\"eval_count\":9}",
+        );
+        let (text, decoder) = decode(Protocol::Ollama, &[stream.as_bytes()]).unwrap();
+        assert_eq!(text, "小蓝鸟");
+        assert!(decoder.is_done());
+        assert_eq!(tokens(&decoder), Some((26, 9)));
+
+        let (_, decoder) = decode(Protocol::Ollama, &[b"{\"message\":{\"content\":\"a\"},\"done\":false}\n"]).unwrap();
+        assert!(!decoder.is_complete());
+        assert_eq!(tokens(&decoder), None);
+    }
+
+    #[test]
+    fn multibyte_character_split_across_feeds() {
+        let line = "data: {\"choices\":[{\"delta\":{\"content\":\"蓝鸟\"}}]}\n".as_bytes();
+        // 切在「蓝」的三个字节中间
+        let cut = line.iter().position(|b| *b >= 0x80).unwrap() + 1;
+        let mut decoder = StreamDecoder::new(Protocol::OpenAI);
+        assert!(decoder.feed(&line[..cut]).unwrap().is_empty());
+        assert_eq!(decoder.feed(&line[cut..]).unwrap(), vec!["蓝鸟".to_string()]);
+    }
+
+    #[test]
+    fn openai_finish_reason_completes_without_done_marker() {
+        let stream = concat!(
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"content\":\"\"},\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"早上好\"},\"finish_reason\":null}]}\n\n",
+            ": keep-alive\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":30,\"completion_tokens\":4}}\n\n",
+        );
+        let (text, decoder) = decode(Protocol::OpenAI, &[stream.as_bytes()]).unwrap();
+        assert_eq!(text, "早上好");
+        assert!(!decoder.is_done() && decoder.is_complete() && !decoder.is_filtered());
+        assert_eq!(tokens(&decoder), Some((30, 4)));
+
+        let cut_off = "data: {\"choices\":[{\"delta\":{\"content\":\"早上\"},\"finish_reason\":null}]}\n";
+        let (_, decoder) = decode(Protocol::OpenAI, &[cut_off.as_bytes()]).unwrap();
+        assert!(!decoder.is_complete());
+
+        let filtered = concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"早\"},\"finish_reason\":null}]}\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"content_filter\"}]}\n",
+            "data: [DONE]\n",
+        );
+        let (text, decoder) = decode(Protocol::OpenAI, &[filtered.as_bytes()]).unwrap();
+        assert_eq!(text, "早");
+        assert!(decoder.is_done() && decoder.is_filtered());
+    }
+
+    #[test]
+    fn in_stream_error_objects_fail_the_stream() {
+        let kind = |protocol, stream: &str| decode(protocol, &[stream.as_bytes()]).err().unwrap().kind;
+        assert_eq!(
+            kind(Protocol::OpenAI, "data: {\"error\":{\"message\":\"Rate limit reached\",\"type\":\"rate_limit_error\"}}\n"),
+            ErrorKind::RateLimit
+        );
+        assert_eq!(
+            kind(
+                Protocol::Anthropic,
+                "event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n"
+            ),
+            ErrorKind::Server
+        );
+        assert_eq!(kind(Protocol::Ollama, "{\"error\":\"model \\\"qwen\\\" not found, try pulling it first\"}\n"), ErrorKind::Config);
+        // 正常分片里的 "error": null 不算错误
+        let ok = "data: {\"error\":null,\"choices\":[{\"delta\":{\"content\":\"好\"}}]}\n";
+        assert_eq!(decode(Protocol::OpenAI, &[ok.as_bytes()]).unwrap().0, "好");
+    }
+}
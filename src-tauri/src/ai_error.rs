@@ -0,0 +1,253 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// 重试等待的上限，Retry-After 超过它时直接放弃
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
+
+/// AI 调用失败的类别，前端据此给出对应的处理建议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// API Key 无效、过期或无权限
+    Auth,
+    /// 余额不足或额度用完
+    Quota,
+    /// 请求太频繁
+    RateLimit,
+    /// 连不上服务器或连接中断
+    Network,
+    /// 连接或读取超时
+    Timeout,
+    /// 内容被安全策略拦截
+    ContentFilter,
+    /// 服务端 5xx 或过载
+    Server,
+    /// 接口地址、模型等配置有误
+    Config,
+    Cancelled,
+    Other,
+}
+
+impl ErrorKind {
+    /// 给用户看的处理建议
+    pub fn hint(self) -> &'static str {
+        match self {
+            ErrorKind::Auth => "API Key 无效或已过期，请在设置中检查",
+            ErrorKind::Quota => "账户余额或额度不足，请充值或换一个引擎",
+            ErrorKind::RateLimit => "请求太频繁，请稍后再试",
+            ErrorKind::Network => "网络连接失败，请检查网络或接口地址",
+            ErrorKind::Timeout => "请求超时，可在设置中调大超时时间",
+            ErrorKind::ContentFilter => "内容被模型的安全策略拦截，请调整内容后重试",
+            ErrorKind::Server => "AI 服务暂时不可用，请稍后再试",
+            ErrorKind::Config => "接口配置有误，请检查接口地址和模型名",
+            ErrorKind::Cancelled => "已取消",
+            ErrorKind::Other => "AI 调用失败",
+        }
+    }
+
+    /// 值得自动重试的错误（还没有输出任何内容时）
+    pub fn retryable(self) -> bool {
+        matches!(self, ErrorKind::RateLimit | ErrorKind::Network | ErrorKind::Timeout | ErrorKind::Server)
+    }
+}
+
+/// 分类后的 AI 错误，detail 为接口返回的原始说明（已截短）
+#[derive(Debug, Clone, Serialize)]
+pub struct AiFailure {
+    pub kind: ErrorKind,
+    pub detail: String,
+    /// 服务端要求的等待时间（秒），来自 Retry-After
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+}
+
+impl AiFailure {
+    pub fn new(kind: ErrorKind, detail: impl Into<String>) -> Self {
+        Self { kind, detail: shorten(&detail.into()), retry_after: None }
+    }
+
+    /// 按 HTTP 状态码和响应体分类
+    pub fn from_response(status: StatusCode, headers: &HeaderMap, body: &str) -> Self {
+        let detail = body_message(body);
+        let lower = detail.to_lowercase();
+        let kind = match status.as_u16() {
+            401 | 403 => ErrorKind::Auth,
+            402 => ErrorKind::Quota,
+            429 if mentions_quota(&lower) => ErrorKind::Quota,
+            429 => ErrorKind::RateLimit,
+            404 => ErrorKind::Config,
+            400 | 422 if mentions_filter(&lower) => ErrorKind::ContentFilter,
+            400 | 422 => ErrorKind::Config,
+            408 => ErrorKind::Timeout,
+            // 529 是 Anthropic 的过载
+            500..=599 => ErrorKind::Server,
+            _ => ErrorKind::Other,
+        };
+        let mut failure = Self::new(kind, format!("{} {}", status.as_u16(), detail));
+        failure.retry_after = retry_after(headers);
+        failure
+    }
+
+    pub fn from_reqwest(e: &reqwest::Error) -> Self {
+        let kind = if e.is_timeout() { ErrorKind::Timeout } else { ErrorKind::Network };
+        Self::new(kind, e.to_string())
+    }
+
+    /// 流中途返回的错误事件，如 {"error": {"type": "overloaded_error", "message": ...}}
+    pub fn from_stream_error(error: &serde_json::Value) -> Self {
+        let message = error
+            .as_str()
+            .or_else(|| error.get("message").and_then(|m| m.as_str()))
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string());
+        let code = error
+            .get("type")
+            .or_else(|| error.get("code"))
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let lower = format!("{} {}", code, message.to_lowercase());
+        let kind = if lower.contains("auth") || lower.contains("permission") || lower.contains("api key") {
+            ErrorKind::Auth
+        } else if mentions_quota(&lower) {
+            ErrorKind::Quota
+        } else if lower.contains("rate_limit") || lower.contains("rate limit") {
+            ErrorKind::RateLimit
+        } else if mentions_filter(&lower) {
+            ErrorKind::ContentFilter
+        } else if lower.contains("overloaded") || lower.contains("server") {
+            ErrorKind::Server
+        } else if lower.contains("not found") || lower.contains("invalid") {
+            ErrorKind::Config
+        } else {
+            ErrorKind::Other
+        };
+        Self::new(kind, message)
+    }
+
+    /// 第 attempt 次重试（从 0 开始）前的等待：优先 Retry-After，否则 1s、2s、4s…… 指数退避
+    ///
+    /// 返回 None 表示等待太久，不再重试
+    pub fn backoff(&self, attempt: u32) -> Option<Duration> {
+        let wait = match self.retry_after {
+            Some(secs) => Duration::from_secs(secs),
+            None => Duration::from_secs(1u64 << attempt.min(5)),
+        };
+        (wait <= MAX_RETRY_WAIT).then_some(wait)
+    }
+}
+
+impl fmt::Display for AiFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.detail.is_empty() || self.kind == ErrorKind::Cancelled {
+            write!(f, "{}", self.kind.hint())
+        } else {
+            write!(f, "{}（{}）", self.kind.hint(), self.detail)
+        }
+    }
+}
+
+/// 配置检查等本地错误
+impl From<String> for AiFailure {
+    fn from(detail: String) -> Self {
+        Self::new(ErrorKind::Config, detail)
+    }
+}
+
+fn mentions_quota(lower: &str) -> bool {
+    ["quota", "insufficient", "balance", "billing", "credit"].iter().any(|w| lower.contains(w))
+}
+
+fn mentions_filter(lower: &str) -> bool {
+    ["content_filter", "content filter", "content policy", "safety", "moderation", "sensitive"]
+        .iter()
+        .any(|w| lower.contains(w))
+}
+
+/// 从 JSON 错误体中取出 message，取不到就用原文
+fn body_message(body: &str) -> String {
+    let parsed: Option<serde_json::Value> = serde_json::from_str(body).ok();
+    parsed
+        .as_ref()
+        .and_then(|v| v.get("error"))
+        .and_then(|e| e.as_str().or_else(|| e.get("message").and_then(|m| m.as_str())))
+        .map(str::to_string)
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+/// Retry-After 可以是秒数或 HTTP 日期
+fn retry_after(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return Some(secs.max(0.0).ceil() as u64);
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let secs = (at.timestamp() - chrono::Utc::now().timestamp()).max(0);
+    Some(secs as u64)
+}
+
+fn shorten(text: &str) -> String {
+    const MAX: usize = 200;
+    let text = text.trim();
+    if text.chars().count() <= MAX {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(MAX).collect();
+    format!("{}…", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn respond(status: u16, retry: Option<&str>, body: &str) -> AiFailure {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = retry {
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        }
+        AiFailure::from_response(StatusCode::from_u16(status).unwrap(), &headers, body)
+    }
+
+    #[test]
+    fn classifies_responses_by_status_and_body() {
+        assert_eq!(respond(401, None, "").kind, ErrorKind::Auth);
+        assert_eq!(respond(402, None, "").kind, ErrorKind::Quota);
+        let quota = respond(429, None, r#"{"error":{"message":"You exceeded your current quota","type":"insufficient_quota"}}"#);
+        assert_eq!(quota.kind, ErrorKind::Quota);
+        assert_eq!(quota.detail, "429 You exceeded your current quota");
+        assert_eq!(respond(429, None, r#"{"error":"slow down"}"#).kind, ErrorKind::RateLimit);
+        assert_eq!(respond(400, None, r#"{"error":{"message":"blocked by content_filter"}}"#).kind, ErrorKind::ContentFilter);
+        assert_eq!(respond(400, None, r#"{"error":{"message":"unknown model"}}"#).kind, ErrorKind::Config);
+        assert_eq!(respond(404, None, "").kind, ErrorKind::Config);
+        let overloaded = respond(529, None, "  Overloaded \n");
+        assert_eq!(overloaded.kind, ErrorKind::Server);
+        assert_eq!(overloaded.detail, "529 Overloaded");
+        assert!(overloaded.kind.retryable() && !quota.kind.retryable());
+    }
+
+    #[test]
+    fn retry_after_seconds_and_dates() {
+        assert_eq!(respond(429, Some("7"), "").retry_after, Some(7));
+        assert_eq!(respond(429, Some("1.5"), "").retry_after, Some(2));
+        assert_eq!(respond(429, Some("soon"), "").retry_after, None);
+        let at = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let secs = respond(503, Some(&at), "").retry_after.unwrap();
+        assert!((29..=30).contains(&secs), "{}", secs);
+        let past = (chrono::Utc::now() - chrono::Duration::seconds(30)).to_rfc2822();
+        assert_eq!(respond(503, Some(&past), "").retry_after, Some(0));
+    }
+
+    #[test]
+    fn backoff_prefers_retry_after_and_gives_up_when_too_long() {
+        assert_eq!(respond(429, Some("7"), "").backoff(3), Some(Duration::from_secs(7)));
+        assert_eq!(respond(429, Some("120"), "").backoff(0), None);
+        let plain = respond(500, None, "");
+        assert_eq!(plain.backoff(0), Some(Duration::from_secs(1)));
+        assert_eq!(plain.backoff(2), Some(Duration::from_secs(4)));
+        assert_eq!(plain.backoff(9), Some(Duration::from_secs(32)));
+    }
+}
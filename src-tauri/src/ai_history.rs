@@ -0,0 +1,132 @@
+use crate::ai_client::Feature;
+use chrono::{Duration, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 与 ai_usage 的时间格式一致，字符串比较即时间先后
+const TS_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+/// 每个功能最多保留的记录数，超出后删除最旧的
+const MAX_ENTRIES_PER_FEATURE: usize = 200;
+/// 缓存有效期上限（小时），防止配置里的超大值溢出
+const MAX_CACHE_HOURS: u64 = 24 * 365;
+
+/// ai_history/<功能>.jsonl 中的一行：一次 AI 生成的输入和输出
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// 生成时的请求 id
+    pub id: String,
+    pub ts: String,
+    pub feature: Feature,
+    /// 系统提示和用户消息的摘要，相同提示词的值相同
+    pub prompt_hash: String,
+    /// 引擎 id
+    pub provider: String,
+    pub model: String,
+    pub system: String,
+    pub user: String,
+    pub text: String,
+    /// 流中途断开，只收到部分内容（不会用作缓存）
+    #[serde(default)]
+    pub incomplete: bool,
+}
+
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+fn feature_path(data_dir: &PathBuf, feature: Feature) -> PathBuf {
+    data_dir.join("ai_history").join(format!("{}.jsonl", feature.key()))
+}
+
+/// 提示词摘要：sha256(system \0 user) 的前 16 字节
+pub fn prompt_hash(system: &str, user_msg: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(system.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(user_msg.as_bytes());
+    hasher.finalize().iter().take(16).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 追加一条生成记录，超出保留上限时删除最旧的
+pub fn record(data_dir: &PathBuf, entry: &HistoryEntry) -> Result<(), String> {
+    let path = feature_path(data_dir, entry.feature);
+    let _guard = HISTORY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    writeln!(f, "{}", line).map_err(|e| e.to_string())?;
+    drop(f);
+    trim(&path)
+}
+
+/// 只保留最新的 MAX_ENTRIES_PER_FEATURE 行，先写临时文件再替换
+fn trim(path: &PathBuf) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() <= MAX_ENTRIES_PER_FEATURE {
+        return Ok(());
+    }
+    let kept = lines[lines.len() - MAX_ENTRIES_PER_FEATURE..].join("\n") + "\n";
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, kept).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+/// 某功能的全部记录，按时间先后
+fn load_feature(data_dir: &PathBuf, feature: Feature) -> Vec<HistoryEntry> {
+    let content = match fs::read_to_string(feature_path(data_dir, feature)) {
+        Ok(c) => c,
+        Err(_) => return vec![],
+    };
+    content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l.trim()).ok())
+        .collect()
+}
+
+/// 最近的生成记录，新的在前；feature 为 None 时列出所有功能
+pub fn list(data_dir: &PathBuf, feature: Option<Feature>, limit: usize) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = match feature {
+        Some(f) => load_feature(data_dir, f),
+        None => Feature::ALL.into_iter().flat_map(|f| load_feature(data_dir, f)).collect(),
+    };
+    entries.sort_by(|a, b| b.ts.cmp(&a.ts));
+    entries.truncate(limit);
+    entries
+}
+
+/// 按请求 id 取回一条记录
+pub fn get(data_dir: &PathBuf, feature: Feature, id: &str) -> Result<HistoryEntry, String> {
+    load_feature(data_dir, feature)
+        .into_iter()
+        .find(|e| e.id == id)
+        .ok_or_else(|| format!("没有找到生成记录 {}", id))
+}
+
+/// 同一引擎、同一模型在 max_age_hours 内对相同提示词的最近一次完整结果
+pub fn cached(
+    data_dir: &PathBuf,
+    feature: Feature,
+    provider: &str,
+    model: &str,
+    hash: &str,
+    max_age_hours: u64,
+) -> Option<HistoryEntry> {
+    let hours = max_age_hours.min(MAX_CACHE_HOURS) as i64;
+    let since = (Local::now().naive_local() - Duration::hours(hours)).format(TS_FORMAT).to_string();
+    load_feature(data_dir, feature).into_iter().rev().find(|e| {
+        !e.incomplete
+            && e.prompt_hash == hash
+            && e.provider == provider
+            && e.model == model
+            && e.ts >= since
+    })
+}
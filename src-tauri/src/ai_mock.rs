@@ -0,0 +1,274 @@
+use crate::ai_client::Feature;
+use crate::ai_error::{AiFailure, ErrorKind};
+use crate::ai_provider::{AIProvider, Protocol};
+use crate::prompts;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// 模拟引擎的设置，写在引擎的 mock 字段里
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockSettings {
+    /// 按功能自定义回复模板，可用 {feature} {model} {date} {time} {user_chars} {user_head}；没写的功能用内置回复
+    #[serde(default)]
+    pub responses: BTreeMap<String, String>,
+    /// 每个分片的字数
+    #[serde(default = "default_chunk_chars")]
+    pub chunk_chars: usize,
+    /// 分片之间的间隔（毫秒）
+    #[serde(default = "default_delay_ms")]
+    pub delay_ms: u64,
+    /// 注入的故障类别，为空表示正常返回
+    #[serde(default)]
+    pub fail: Option<ErrorKind>,
+    /// 输出多少个分片后触发故障，0 表示一开始就失败
+    #[serde(default)]
+    pub fail_after_chunks: usize,
+    /// 回复中 {date} {time} 使用的固定时间，不取系统时间以保证回复可重复
+    #[serde(default = "default_now")]
+    pub now: String,
+}
+
+fn default_chunk_chars() -> usize { 8 }
+fn default_delay_ms() -> u64 { 40 }
+fn default_now() -> String { "2026-01-01T09:00:00".to_string() }
+
+impl Default for MockSettings {
+    fn default() -> Self {
+        Self {
+            responses: BTreeMap::new(),
+            chunk_chars: default_chunk_chars(),
+            delay_ms: default_delay_ms(),
+            fail: None,
+            fail_after_chunks: 0,
+            now: default_now(),
+        }
+    }
+}
+
+/// 命令行 --mock 使用的临时模拟引擎，不读写配置
+pub fn provider() -> AIProvider {
+    AIProvider {
+        id: "mock".to_string(),
+        name: "模拟引擎".to_string(),
+        protocol: Protocol::Mock,
+        api_key: String::new(),
+        base_url: String::new(),
+        model: "mock".to_string(),
+        temperature: 0.7,
+        enabled: true,
+        connect_timeout_secs: 10,
+        read_timeout_secs: 60,
+        max_retries: 0,
+        monthly_budget_tokens: 0,
+        stream_usage: true,
+        mock: None,
+    }
+}
+
+/// 内置回复，格式与各功能前端的解析方式一致（小剧场的 --- 分支、单词的 JSON、炼金的标题行）
+fn canned(feature: Feature) -> &'static str {
+    match feature {
+        Feature::Diary => concat!(
+            "# {date}\n\n",
+            "（模拟日记）今天大部分时间都在电脑前，收到了 {user_chars} 字的素材。\n",
+            "第一行是：{user_head}\n\n",
+            "*记录时间：{time}*",
+        ),
+        Feature::Theater => concat!(
+            "（模拟番外）主人今天在屏幕前度过了平凡又忙碌的一天，小蓝鸟在窗台上打了个哈欠。\n",
+            "---\n",
+            "## 如果今天没有开电脑\n",
+            "主人去了海边，小蓝鸟第一次见到了真正的浪花。\n\n",
+            "## 如果小蓝鸟会写代码\n",
+            "它会先给自己写一个自动投喂脚本。",
+        ),
+        Feature::Vocab => concat!(
+            "[{\"word\":\"mock\",\"examples\":[",
+            "{\"style\":\"日常\",\"text\":\"Don't mock me, I'm trying my best.\"},",
+            "{\"style\":\"网文\",\"text\":\"Mock the heavens, and the heavens mock back.\"},",
+            "{\"style\":\"程序员\",\"text\":\"We mock the API because the API mocks us.\"}]}]",
+        ),
+        Feature::Alchemy => concat!(
+            "模拟炼金：雨夜的路灯\n\n",
+            "（模拟素材）零散的灵感在坩埚里碰撞，变成了一个关于等待与回声的小故事。",
+        ),
+        Feature::Writing => concat!(
+            "1. 顺势推进：让主角立刻做出选择。（模拟建议）\n",
+            "2. 插入回忆：用一段往事解释动机。\n",
+            "3. 制造意外：安排一个不速之客。",
+        ),
+        Feature::Dream => "（模拟解梦）这个梦说明你最近在期待一些新鲜事，别担心，它是个好兆头。",
+    }
+}
+
+/// 生成模拟回复：自定义模板优先，其次内置回复；结果只取决于设置和输入，便于测试
+pub fn response(settings: &MockSettings, feature: Feature, model: &str, user_msg: &str) -> String {
+    let template = settings
+        .responses
+        .get(feature.key())
+        .map(String::as_str)
+        .unwrap_or_else(|| canned(feature));
+    let (date, time) = settings.now.split_once('T').unwrap_or((settings.now.as_str(), ""));
+    let vars = prompts::vars([
+        ("feature", feature.key().to_string()),
+        ("model", model.to_string()),
+        ("date", date.to_string()),
+        ("time", time.get(..5).unwrap_or(time).to_string()),
+        ("user_chars", user_msg.chars().count().to_string()),
+        ("user_head", user_msg.lines().next().unwrap_or_default().to_string()),
+    ]);
+    prompts::substitute(template, &vars)
+}
+
+/// 从 chat_request 构造好的请求体中取出模型和最后一条用户消息
+fn parse_request(request: &reqwest::Request) -> Result<(String, String), AiFailure> {
+    let body = request
+        .body()
+        .and_then(|b| b.as_bytes())
+        .ok_or_else(|| AiFailure::new(ErrorKind::Config, "模拟引擎收到的请求没有请求体"))?;
+    let v: serde_json::Value =
+        serde_json::from_slice(body).map_err(|e| AiFailure::new(ErrorKind::Config, format!("请求体不是 JSON: {}", e)))?;
+    let user_msg = v["messages"]
+        .as_array()
+        .and_then(|m| m.iter().rev().find(|m| m["role"] == "user"))
+        .and_then(|m| m["content"].as_str())
+        .ok_or_else(|| AiFailure::new(ErrorKind::Config, "请求中没有用户消息"))?;
+    Ok((v["model"].as_str().unwrap_or_default().to_string(), user_msg.to_string()))
+}
+
+/// 按设置分片流式输出对 request 的模拟回复，收到的文本追加到 received；注入故障时在指定分片后返回错误
+pub async fn stream<F>(
+    settings: &MockSettings,
+    feature: Feature,
+    request: &reqwest::Request,
+    received: &mut String,
+    on_chunk: &mut F,
+) -> Result<(), AiFailure>
+where
+    F: FnMut(&str),
+{
+    let (model, user_msg) = parse_request(request)?;
+    let text = response(settings, feature, &model, &user_msg);
+    let chars: Vec<char> = text.chars().collect();
+    for (i, piece) in chars.chunks(settings.chunk_chars.max(1)).enumerate() {
+        if let Some(kind) = settings.fail.filter(|_| i == settings.fail_after_chunks) {
+            return Err(AiFailure::new(kind, "模拟引擎注入的故障"));
+        }
+        if i > 0 && settings.delay_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(settings.delay_ms)).await;
+        }
+        let piece: String = piece.iter().collect();
+        received.push_str(&piece);
+        on_chunk(&piece);
+    }
+    // 分片数不足时也要触发故障
+    if let Some(kind) = settings.fail {
+        return Err(AiFailure::new(kind, "模拟引擎注入的故障"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_client;
+
+    fn settings(chunk_chars: usize, fail: Option<ErrorKind>, fail_after_chunks: usize) -> MockSettings {
+        MockSettings { chunk_chars, delay_ms: 0, fail, fail_after_chunks, ..MockSettings::default() }
+    }
+
+    fn request(user_msg: &str) -> reqwest::Request {
+        provider().chat_request(&reqwest::Client::new(), "系统提示", user_msg, true).unwrap().build().unwrap()
+    }
+
+    #[test]
+    fn response_is_deterministic() {
+        let s = MockSettings::default();
+        let a = response(&s, Feature::Diary, "mock", "写代码\n开会");
+        assert_eq!(a, response(&s, Feature::Diary, "mock", "写代码\n开会"));
+        assert!(a.starts_with("# 2026-01-01"));
+        assert!(a.contains("第一行是：写代码") && a.contains("*记录时间：09:00*"));
+    }
+
+    #[test]
+    fn vocab_reply_is_valid_json() {
+        let text = response(&MockSettings::default(), Feature::Vocab, "mock", "");
+        assert!(serde_json::from_str::<serde_json::Value>(&text).unwrap().is_array());
+    }
+
+    #[test]
+    fn request_body_goes_through_chat_request() {
+        let req = request("梦到飞");
+        assert_eq!(req.url().as_str(), "http://mock.invalid/v1/chat/completions");
+        assert_eq!(parse_request(&req).unwrap(), ("mock".to_string(), "梦到飞".to_string()));
+    }
+
+    #[test]
+    fn stream_options_follow_provider_setting() {
+        let body = |p: &AIProvider| -> serde_json::Value {
+            let req = p.chat_request(&reqwest::Client::new(), "", "x", true).unwrap().build().unwrap();
+            serde_json::from_slice(req.body().unwrap().as_bytes().unwrap()).unwrap()
+        };
+        let mut p = provider();
+        assert_eq!(body(&p)["stream_options"]["include_usage"], true);
+        p.stream_usage = false;
+        assert!(body(&p).get("stream_options").is_none());
+    }
+
+    #[tokio::test]
+    async fn streams_in_chunks() {
+        let mut s = settings(4, None, 0);
+        s.responses.insert("dream".to_string(), "{user_head}：{user_chars} 字，{model}".to_string());
+        let (mut received, mut chunks) = (String::new(), Vec::new());
+        stream(&s, Feature::Dream, &request("梦到飞"), &mut received, &mut |c: &str| chunks.push(c.to_string()))
+            .await
+            .unwrap();
+        assert_eq!(received, "梦到飞：3 字，mock");
+        assert_eq!(chunks, ["梦到飞：", "3 字，", "mock"]);
+    }
+
+    #[tokio::test]
+    async fn injects_failure_after_chunks() {
+        let s = settings(2, Some(ErrorKind::Network), 2);
+        let mut received = String::new();
+        let err = stream(&s, Feature::Dream, &request("x"), &mut received, &mut |_: &str| {}).await.unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Network);
+        assert_eq!(received.chars().count(), 4);
+
+        // 分片不够时在结尾失败
+        let s = settings(1000, Some(ErrorKind::Server), 5);
+        let mut received = String::new();
+        let err = stream(&s, Feature::Dream, &request("x"), &mut received, &mut |_: &str| {}).await.unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Server);
+        assert_eq!(received, response(&s, Feature::Dream, "mock", "x"));
+    }
+
+    #[tokio::test]
+    async fn stream_chat_marks_partial_output_incomplete() {
+        let mut p = provider();
+        p.mock = Some(settings(3, Some(ErrorKind::Network), 1));
+        let done = ai_client::stream_chat(&p, Feature::Alchemy, "系统提示", "素材", |_| {}, |_, _, _| {}).await.unwrap();
+        assert_eq!(done.text.chars().count(), 3);
+        assert_eq!(done.incomplete.map(|f| f.kind), Some(ErrorKind::Network));
+
+        p.mock = Some(settings(3, Some(ErrorKind::Auth), 0));
+        let err = ai_client::stream_chat(&p, Feature::Alchemy, "系统提示", "素材", |_| {}, |_, _, _| {}).await.unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Auth);
+    }
+
+    #[tokio::test]
+    async fn retries_are_reported_before_waiting() {
+        let mut p = provider();
+        p.max_retries = 1;
+        p.mock = Some(settings(3, Some(ErrorKind::Network), 0));
+        let mut retries = Vec::new();
+        let err = ai_client::stream_chat(&p, Feature::Dream, "", "x", |_| {}, |attempt, wait, failure| {
+            retries.push((attempt, wait.as_secs(), failure.kind))
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Network);
+        assert_eq!(retries, [(1, 1, ErrorKind::Network)]);
+    }
+}
@@ -1,13 +1,51 @@
-use futures_util::StreamExt;
+use crate::ai_error::AiFailure;
+use crate::ai_mock::MockSettings;
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter};
+use std::time::Duration;
+
+/// Anthropic Messages API 版本头
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Anthropic 要求必填 max_tokens
+const ANTHROPIC_MAX_TOKENS: u32 = 4096;
+/// 测试连接的总超时
+const TEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 接口协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    /// OpenAI 兼容的 /chat/completions（SSE）
+    #[default]
+    OpenAI,
+    /// Anthropic Messages API 的 /messages（SSE，content_block_delta 事件）
+    Anthropic,
+    /// Ollama 本地的 /api/chat（NDJSON），不需要 API Key
+    Ollama,
+    /// 离线模拟：照常构造请求但不发出，按固定模板分片返回，用于调试和测试
+    Mock,
+}
+
+impl Protocol {
+    /// base_url 留空时使用的地址
+    pub fn default_base_url(self) -> &'static str {
+        match self {
+            Protocol::OpenAI => "https://api.openai.com/v1",
+            Protocol::Anthropic => "https://api.anthropic.com/v1",
+            Protocol::Ollama => "http://localhost:11434",
+            // 模拟引擎只构造请求，不会真正发出
+            Protocol::Mock => "http://mock.invalid/v1",
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AIProvider {
     pub id: String,
     pub name: String,
     #[serde(default)]
+    pub protocol: Protocol,
+    #[serde(default)]
     pub api_key: String,
     #[serde(default)]
     pub base_url: String,
@@ -17,223 +55,205 @@ pub struct AIProvider {
     pub temperature: f64,
     #[serde(default = "default_enabled")]
     pub enabled: bool,
+    /// 建立连接的超时（秒）
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    /// 两次收到数据之间的最长间隔（秒），本地大模型首字较慢时可调大
+    #[serde(default = "default_read_timeout")]
+    pub read_timeout_secs: u64,
+    /// 限流、网络和服务端错误的最多重试次数
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// 每月 token 预算，用完后拒绝调用；0 表示不限
+    #[serde(default)]
+    pub monthly_budget_tokens: u64,
+    /// OpenAI 兼容流式请求附带 stream_options.include_usage 以取得用量；不认识该字段的兼容接口需关闭，改为按字数估算
+    #[serde(default = "default_stream_usage")]
+    pub stream_usage: bool,
+    /// 模拟引擎的回复、分片和故障注入设置，只在 protocol 为 mock 时使用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mock: Option<MockSettings>,
 }
 
 fn default_temperature() -> f64 { 0.7 }
 fn default_enabled() -> bool { true }
-
-#[derive(Serialize)]
-struct ChatRequest {
-    model: String,
-    messages: Vec<ChatMessage>,
-    temperature: f64,
-    stream: bool,
-}
-
-#[derive(Serialize)]
-struct ChatMessage {
-    role: String,
-    content: String,
-}
+fn default_connect_timeout() -> u64 { 10 }
+fn default_read_timeout() -> u64 { 60 }
+fn default_max_retries() -> u32 { 3 }
+fn default_stream_usage() -> bool { true }
 
 #[derive(Deserialize)]
-struct StreamChunk {
-    choices: Vec<StreamChoice>,
-}
-
-#[derive(Deserialize)]
-struct StreamChoice {
-    delta: Delta,
+struct OllamaTags {
+    #[serde(default)]
+    models: Vec<OllamaModel>,
 }
 
 #[derive(Deserialize)]
-struct Delta {
-    #[serde(default)]
-    content: Option<String>,
+struct OllamaModel {
+    name: String,
 }
 
 impl AIProvider {
-    pub async fn chat_stream(
-        &self,
-        app: &AppHandle,
-        system: &str,
-        user_msg: &str,
-    ) -> Result<String, String> {
-        if self.api_key.is_empty() {
-            return Err("API Key 未配置".to_string());
-        }
-        let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
-
-        let mut headers = HeaderMap::new();
-        let auth = HeaderValue::from_str(&format!("Bearer {}", self.api_key))
-            .map_err(|e| format!("invalid API key header: {}", e))?;
-        headers.insert(AUTHORIZATION, auth);
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-        let body = ChatRequest {
-            model: self.model.clone(),
-            messages: vec![
-                ChatMessage { role: "system".to_string(), content: system.to_string() },
-                ChatMessage { role: "user".to_string(), content: user_msg.to_string() },
-            ],
-            temperature: self.temperature,
-            stream: true,
-        };
-
-        let client = reqwest::Client::new();
-        let resp = client.post(&url)
-            .headers(headers)
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("request failed: {}", e))?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            return Err(format!("API returned {}: {}", status, text));
-        }
-
-        let mut full_text = String::new();
-        let mut stream = resp.bytes_stream();
-        let mut pending = String::new();
-        let mut done = false;
+    /// 按引擎的超时设置创建 HTTP 客户端
+    pub fn client(&self) -> Result<reqwest::Client, String> {
+        reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs.max(1)))
+            .read_timeout(Duration::from_secs(self.read_timeout_secs.max(1)))
+            .build()
+            .map_err(|e| e.to_string())
+    }
 
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk.map_err(|e| format!("stream read failed: {}", e))?;
-            pending.push_str(&String::from_utf8_lossy(&chunk));
+    fn endpoint(&self, path: &str) -> String {
+        let base = self.base_url.trim();
+        let base = if base.is_empty() { self.protocol.default_base_url() } else { base };
+        format!("{}/{}", base.trim_end_matches('/'), path)
+    }
 
-            while let Some(pos) = pending.find('\n') {
-                let line = pending[..pos].trim().to_string();
-                pending.drain(..=pos);
-                if !line.starts_with("data: ") {
-                    continue;
-                }
-                let data = &line[6..];
-                if data == "[DONE]" {
-                    done = true;
-                    break;
-                }
+    /// 模拟引擎的设置，未填写时用默认值
+    pub fn mock_settings(&self) -> MockSettings {
+        self.mock.clone().unwrap_or_default()
+    }
 
-                if let Ok(parsed) = serde_json::from_str::<StreamChunk>(data) {
-                    if let Some(choice) = parsed.choices.first() {
-                        if let Some(ref content) = choice.delta.content {
-                            full_text.push_str(content);
-                            let _ = app.emit("ai-chunk", content.clone());
-                        }
+    /// 按协议生成请求头，密钥引用在这里解析为明文
+    fn headers(&self) -> Result<HeaderMap, String> {
+        let api_key = crate::secrets::resolve(&self.api_key)?;
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        match self.protocol {
+            Protocol::OpenAI | Protocol::Ollama => {
+                if api_key.is_empty() {
+                    if self.protocol == Protocol::OpenAI {
+                        return Err("API Key 未配置".to_string());
                     }
+                } else {
+                    let auth = HeaderValue::from_str(&format!("Bearer {}", api_key))
+                        .map_err(|e| format!("API key 无效: {}", e))?;
+                    headers.insert(AUTHORIZATION, auth);
                 }
             }
-
-            if done {
-                break;
-            }
-        }
-
-        let tail = pending.trim();
-        if !done && tail.starts_with("data: ") {
-            let data = &tail[6..];
-            if data != "[DONE]" {
-                if let Ok(parsed) = serde_json::from_str::<StreamChunk>(data) {
-                    if let Some(choice) = parsed.choices.first() {
-                        if let Some(ref content) = choice.delta.content {
-                            full_text.push_str(content);
-                            let _ = app.emit("ai-chunk", content.clone());
-                        }
-                    }
+            Protocol::Anthropic => {
+                if api_key.is_empty() {
+                    return Err("API Key 未配置".to_string());
                 }
+                let key = HeaderValue::from_str(&api_key).map_err(|e| format!("API key 无效: {}", e))?;
+                headers.insert("x-api-key", key);
+                headers.insert("anthropic-version", HeaderValue::from_static(ANTHROPIC_VERSION));
             }
+            Protocol::Mock => {}
         }
+        Ok(headers)
+    }
 
-        let _ = app.emit("ai-done", ());
-
-        if full_text.trim().is_empty() {
-            return Err("API 返回空内容".to_string());
+    /// 构造一次对话请求（system 可为空），stream 决定是否流式返回
+    pub fn chat_request(
+        &self,
+        client: &reqwest::Client,
+        system: &str,
+        user_msg: &str,
+        stream: bool,
+    ) -> Result<reqwest::RequestBuilder, String> {
+        let headers = self.headers()?;
+        let mut messages = Vec::new();
+        // Anthropic 的 system 是顶层字段，不放在 messages 里
+        if !system.is_empty() && self.protocol != Protocol::Anthropic {
+            messages.push(serde_json::json!({ "role": "system", "content": system }));
         }
-        Ok(full_text.trim().to_string())
+        messages.push(serde_json::json!({ "role": "user", "content": user_msg }));
+
+        let (url, body) = match self.protocol {
+            // 模拟引擎按 OpenAI 兼容格式构造请求，回复时从请求体里取模型和用户消息
+            Protocol::OpenAI | Protocol::Mock => {
+                let mut body = serde_json::json!({
+                    "model": self.model,
+                    "messages": messages,
+                    "temperature": self.temperature,
+                    "stream": stream,
+                });
+                // 流式时默认不返回用量，要求在最后一个分片里附上（引擎关闭 stream_usage 时不发）
+                if stream && self.stream_usage {
+                    body["stream_options"] = serde_json::json!({ "include_usage": true });
+                }
+                (self.endpoint("chat/completions"), body)
+            }
+            Protocol::Anthropic => {
+                let mut body = serde_json::json!({
+                    "model": self.model,
+                    "max_tokens": ANTHROPIC_MAX_TOKENS,
+                    "messages": messages,
+                    "temperature": self.temperature.min(1.0),
+                    "stream": stream,
+                });
+                if !system.is_empty() {
+                    body["system"] = serde_json::json!(system);
+                }
+                (self.endpoint("messages"), body)
+            }
+            Protocol::Ollama => (
+                self.endpoint("api/chat"),
+                serde_json::json!({
+                    "model": self.model,
+                    "messages": messages,
+                    "stream": stream,
+                    "options": { "temperature": self.temperature },
+                }),
+            ),
+        };
+        Ok(client.post(url).headers(headers).json(&body))
     }
 
+    /// 按协议验证连接：OpenAI 兼容和 Anthropic 发一条最短的对话，Ollama 检查服务在线且模型已下载，模拟引擎只检查能否构造请求
     pub async fn test_connection(&self) -> Result<bool, String> {
-        if self.api_key.is_empty() {
-            return Err("API Key 未配置".to_string());
+        if self.protocol == Protocol::Mock {
+            self.chat_request(&reqwest::Client::new(), "", "Hi", false)?.build().map_err(|e| e.to_string())?;
+            return Ok(true);
         }
-        let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
-
-        let mut headers = HeaderMap::new();
-        let auth = HeaderValue::from_str(&format!("Bearer {}", self.api_key))
-            .map_err(|e| format!("invalid API key header: {}", e))?;
-        headers.insert(AUTHORIZATION, auth);
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
-        let body = ChatRequest {
-            model: self.model.clone(),
-            messages: vec![
-                ChatMessage { role: "user".to_string(), content: "Hi".to_string() },
-            ],
-            temperature: 0.1,
-            stream: false,
-        };
+        if self.model.trim().is_empty() {
+            return Err("模型未配置".to_string());
+        }
+        let client = self.client()?;
 
-        let client = reqwest::Client::builder()
-            .timeout(std::time::Duration::from_secs(15))
-            .build()
-            .map_err(|e| e.to_string())?;
+        if self.protocol == Protocol::Ollama {
+            return self.test_ollama(&client).await;
+        }
 
-        let resp = client.post(&url)
-            .headers(headers)
-            .json(&body)
+        let resp = self
+            .chat_request(&client, "", "Hi", false)?
+            .timeout(TEST_TIMEOUT)
             .send()
             .await
-            .map_err(|e| format!("connection failed: {}", e))?;
-
+            .map_err(|e| AiFailure::from_reqwest(&e).to_string())?;
         if resp.status().is_success() {
             Ok(true)
         } else {
             let status = resp.status();
+            let headers = resp.headers().clone();
             let text = resp.text().await.unwrap_or_default();
-            Err(format!("API returned {}: {}", status, text))
+            Err(AiFailure::from_response(status, &headers, &text).to_string())
         }
     }
-}
 
-pub fn get_writing_provider() -> Result<AIProvider, String> {
-    let cfg_path = crate::config::config_path();
-    let raw: serde_json::Value = if cfg_path.exists() {
-        let content = std::fs::read_to_string(&cfg_path).map_err(|e| e.to_string())?;
-        serde_json::from_str(&content).unwrap_or_default()
-    } else {
-        serde_json::Value::default()
-    };
-
-    let writing_provider_id = raw.get("writing_provider")
-        .and_then(|v| v.as_str())
-        .or_else(|| raw.get("active_provider").and_then(|v| v.as_str()))
-        .unwrap_or("");
-
-    if let Some(providers) = raw.get("ai_providers").and_then(|v| v.as_array()) {
-        if let Some(p) = providers.iter().find(|p| {
-            p.get("id").and_then(|v| v.as_str()) == Some(writing_provider_id)
-        }) {
-            let provider: AIProvider = serde_json::from_value(p.clone())
-                .map_err(|e| format!("parse provider failed: {}", e))?;
-            return Ok(provider);
+    async fn test_ollama(&self, client: &reqwest::Client) -> Result<bool, String> {
+        let resp = client
+            .get(self.endpoint("api/tags"))
+            .headers(self.headers()?)
+            .timeout(TEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("无法连接 Ollama（是否已运行 ollama serve？）: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("Ollama 返回 {}", resp.status()));
+        }
+        let tags: OllamaTags = resp.json().await.map_err(|e| format!("Ollama 响应格式错误: {}", e))?;
+        // 未写标签的模型名默认是 :latest
+        let wanted = self.model.trim();
+        let found = tags
+            .models
+            .iter()
+            .any(|m| m.name == wanted || m.name.strip_suffix(":latest") == Some(wanted));
+        if found {
+            Ok(true)
+        } else {
+            Err(format!("Ollama 中没有模型 {}，请先运行 ollama pull {}", wanted, wanted))
         }
     }
-
-    let api_key = raw.get("api_key").and_then(|v| v.as_str()).unwrap_or("").to_string();
-    let model = raw.get("model").and_then(|v| v.as_str()).unwrap_or("deepseek-chat").to_string();
-
-    if api_key.is_empty() {
-        return Err("请先在设置中配置 AI 接口".to_string());
-    }
-
-    Ok(AIProvider {
-        id: "legacy".to_string(),
-        name: "默认".to_string(),
-        api_key,
-        base_url: "https://api.deepseek.com/v1".to_string(),
-        model,
-        temperature: 0.7,
-        enabled: true,
-    })
 }
@@ -0,0 +1,176 @@
+use crate::activity::ReportPeriod;
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const TS_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// 一次调用的 token 数
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// ai_usage/YYYY-MM.jsonl 中的一行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub ts: String,
+    /// 引擎 id
+    pub provider: String,
+    pub model: String,
+    pub feature: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// 接口没有返回用量，按字数估算
+    #[serde(default)]
+    pub estimated: bool,
+    /// 从发出请求到流结束的耗时（含重试）
+    pub latency_ms: u64,
+    /// 流中途断开，只收到部分内容
+    #[serde(default)]
+    pub incomplete: bool,
+}
+
+impl UsageRecord {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// 按引擎或功能汇总的用量
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageTotal {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// 其中估算的调用次数
+    pub estimated_calls: u64,
+    pub latency_ms: u64,
+}
+
+impl UsageTotal {
+    fn add(&mut self, r: &UsageRecord) {
+        self.calls += 1;
+        self.prompt_tokens += r.prompt_tokens;
+        self.completion_tokens += r.completion_tokens;
+        self.estimated_calls += r.estimated as u64;
+        self.latency_ms += r.latency_ms;
+    }
+}
+
+/// get_ai_usage 的返回：时间段内的明细和汇总
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    pub period: ReportPeriod,
+    pub total: UsageTotal,
+    pub by_provider: BTreeMap<String, UsageTotal>,
+    pub by_feature: BTreeMap<String, UsageTotal>,
+    pub records: Vec<UsageRecord>,
+}
+
+static USAGE_LOCK: Mutex<()> = Mutex::new(());
+
+fn month_path(data_dir: &PathBuf, month: &str) -> PathBuf {
+    data_dir.join("ai_usage").join(format!("{}.jsonl", month))
+}
+
+/// 追加一条用量记录
+pub fn record(data_dir: &PathBuf, rec: &UsageRecord) -> Result<(), String> {
+    let month = rec.ts.get(..7).unwrap_or_default();
+    let path = month_path(data_dir, month);
+    let _guard = USAGE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    let line = serde_json::to_string(rec).map_err(|e| e.to_string())?;
+    writeln!(f, "{}", line).map_err(|e| e.to_string())
+}
+
+pub fn now_ts() -> String {
+    Local::now().format(TS_FORMAT).to_string()
+}
+
+fn load_month(data_dir: &PathBuf, month: &str) -> Vec<UsageRecord> {
+    let content = match fs::read_to_string(month_path(data_dir, month)) {
+        Ok(c) => c,
+        Err(_) => return vec![],
+    };
+    content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l.trim()).ok())
+        .collect()
+}
+
+/// [from, to] 内的所有记录
+pub fn load_between(data_dir: &PathBuf, from: NaiveDate, to: NaiveDate) -> Vec<UsageRecord> {
+    let (from_s, to_s) = (from.to_string(), to.to_string());
+    let mut out = Vec::new();
+    let mut month = from.format("%Y-%m").to_string();
+    let last = to.format("%Y-%m").to_string();
+    while month <= last {
+        out.extend(
+            load_month(data_dir, &month)
+                .into_iter()
+                .filter(|r| r.ts.get(..10).is_some_and(|d| d >= from_s.as_str() && d <= to_s.as_str())),
+        );
+        month = next_month(&month);
+    }
+    out
+}
+
+fn next_month(month: &str) -> String {
+    let (y, m) = month.split_once('-').unwrap_or(("0", "0"));
+    let (y, m) = (y.parse::<i32>().unwrap_or(0), m.parse::<u32>().unwrap_or(0));
+    if m >= 12 {
+        format!("{:04}-01", y + 1)
+    } else {
+        format!("{:04}-{:02}", y, m + 1)
+    }
+}
+
+/// 时间段内的用量汇总，period 格式同报告（2026-03、2026-W10、2026-03-01..2026-03-15）
+pub fn report(data_dir: &PathBuf, period: ReportPeriod) -> UsageReport {
+    let records = load_between(data_dir, period.from, period.to);
+    let mut total = UsageTotal::default();
+    let mut by_provider: BTreeMap<String, UsageTotal> = BTreeMap::new();
+    let mut by_feature: BTreeMap<String, UsageTotal> = BTreeMap::new();
+    for r in &records {
+        total.add(r);
+        by_provider.entry(r.provider.clone()).or_default().add(r);
+        by_feature.entry(r.feature.clone()).or_default().add(r);
+    }
+    UsageReport { period, total, by_provider, by_feature, records }
+}
+
+/// 某引擎本月已用的 token 数
+pub fn month_tokens(data_dir: &PathBuf, provider_id: &str) -> u64 {
+    let month = Local::now().format("%Y-%m").to_string();
+    load_month(data_dir, &month)
+        .iter()
+        .filter(|r| r.provider == provider_id)
+        .map(UsageRecord::total_tokens)
+        .sum()
+}
+
+/// 接口没有返回用量时粗略估算：中日韩字符约 1 字 1 token，其余约 4 字符 1 token
+pub fn estimate_tokens(text: &str) -> u64 {
+    let (mut cjk, mut other) = (0u64, 0u64);
+    for c in text.chars() {
+        if matches!(c as u32, 0x3000..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF) {
+            cjk += 1;
+        } else {
+            other += 1;
+        }
+    }
+    cjk + other.div_ceil(4)
+}
@@ -0,0 +1,108 @@
+/// 开机启动项名称
+const APP_NAME: &str = "xiaolanniao";
+
+fn current_exe() -> Result<String, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("无法获取程序路径: {}", e))?;
+    Ok(exe.to_string_lossy().to_string())
+}
+
+/// 注册或取消开机启动：Linux 写 XDG autostart 桌面文件，Windows 写注册表 Run 键
+pub fn set_launch_on_login(enabled: bool) -> Result<(), String> {
+    platform::set(enabled)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// $XDG_CONFIG_HOME/autostart，未设置时为 ~/.config/autostart
+    fn autostart_dir() -> Result<PathBuf, String> {
+        if let Ok(dir) = std::env::var("XDG_CONFIG_HOME") {
+            if !dir.trim().is_empty() {
+                return Ok(PathBuf::from(dir).join("autostart"));
+            }
+        }
+        let home = std::env::var("HOME").map_err(|_| "找不到 HOME 目录".to_string())?;
+        Ok(PathBuf::from(home).join(".config").join("autostart"))
+    }
+
+    /// 按桌面文件规范写 Exec：先作为带引号的参数转义，整行再按字符串值转义一次反斜杠，最后 % 写成 %%
+    fn exec_value(exe: &str) -> String {
+        let quoted = exe.replace('\\', "\\\\").replace('"', "\\\"").replace('`', "\\`").replace('$', "\\$");
+        let value = format!("\"{}\"", quoted).replace('\\', "\\\\").replace('\n', "\\n");
+        value.replace('%', "%%")
+    }
+
+    pub fn set(enabled: bool) -> Result<(), String> {
+        let path = autostart_dir()?.join(format!("{}.desktop", super::APP_NAME));
+        if !enabled {
+            if path.exists() {
+                fs::remove_file(&path).map_err(|e| e.to_string())?;
+            }
+            return Ok(());
+        }
+        let content = format!(
+            "[Desktop Entry]\nType=Application\nName=小蓝鸟\nExec={}\nTerminal=false\nX-GNOME-Autostart-enabled=true\n",
+            exec_value(&super::current_exe()?)
+        );
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(&path, content).map_err(|e| e.to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::exec_value;
+
+        #[test]
+        fn exec_escapes_both_layers_and_field_codes() {
+            assert_eq!(exec_value("/opt/小蓝鸟/xiaolanniao"), "\"/opt/小蓝鸟/xiaolanniao\"");
+            // 引号 → \"，字符串值中再变成 \\"
+            assert_eq!(exec_value(r#"/a"b"#), r#""/a\\"b""#);
+            assert_eq!(exec_value(r"/a\b"), r#""/a\\\\b""#);
+            assert_eq!(exec_value("/a$b`c"), r#""/a\\$b\\`c""#);
+            assert_eq!(exec_value("/100%/app"), "\"/100%%/app\"");
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+    /// 不弹出控制台窗口
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    pub fn set(enabled: bool) -> Result<(), String> {
+        let mut cmd = Command::new("reg");
+        if enabled {
+            let exe = super::current_exe()?;
+            cmd.args(["add", RUN_KEY, "/v", super::APP_NAME, "/t", "REG_SZ", "/d", &format!("\"{}\"", exe), "/f"]);
+        } else {
+            cmd.args(["delete", RUN_KEY, "/v", super::APP_NAME, "/f"]);
+        }
+        let output = cmd
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .map_err(|e| format!("无法调用 reg: {}", e))?;
+        // 删除不存在的值会失败，取消时忽略
+        if enabled && !output.status.success() {
+            return Err(format!("写入注册表失败: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    pub fn set(enabled: bool) -> Result<(), String> {
+        if enabled {
+            return Err("当前系统暂不支持开机启动".into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 浏览器进程名（已去掉 .exe）
+const BROWSERS: &[&str] = &[
+    "chrome", "chromium", "chromium-browser", "google-chrome", "firefox", "firefox-esr",
+    "msedge", "microsoft-edge", "brave", "brave-browser", "opera", "vivaldi", "safari",
+];
+
+/// 常见网站在标题里的后缀 → 域名
+const TITLE_SUFFIXES: &[(&str, &str)] = &[
+    (" - YouTube", "youtube.com"),
+    ("_哔哩哔哩_bilibili", "bilibili.com"),
+    (" - 哔哩哔哩", "bilibili.com"),
+    (" · GitHub", "github.com"),
+    (" - GitHub", "github.com"),
+    (" · GitLab", "gitlab.com"),
+    (" - Stack Overflow", "stackoverflow.com"),
+    (" - 知乎", "zhihu.com"),
+    (" - 微博", "weibo.com"),
+    (" / X", "x.com"),
+    (" - Gmail", "mail.google.com"),
+    (" - Google 搜索", "google.com"),
+    (" - Google Search", "google.com"),
+    (" - 百度搜索", "baidu.com"),
+    (" - Wikipedia", "wikipedia.org"),
+    (" - 维基百科，自由的百科全书", "wikipedia.org"),
+    (" - Docs.rs", "docs.rs"),
+    (" - Reddit", "reddit.com"),
+    (" - 网易云音乐", "music.163.com"),
+    (" - 豆瓣", "douban.com"),
+    (" - CSDN博客", "csdn.net"),
+];
+
+/// 浏览器扩展上报的当前标签页
+struct Tab {
+    domain: String,
+    title: String,
+    at: Instant,
+}
+
+static LATEST_TAB: Mutex<Option<Tab>> = Mutex::new(None);
+
+/// 扩展上报超过这个时间没更新就不再信任
+const TAB_FRESH: Duration = Duration::from_secs(600);
+
+pub fn is_browser(exe: &str) -> bool {
+    let lower = exe.trim().to_lowercase();
+    let name = lower.strip_suffix(".exe").unwrap_or(&lower);
+    BROWSERS.contains(&name)
+}
+
+/// 从 URL 中取出域名（去掉 www.），不是 http(s) 链接时返回 None
+pub fn host_of(url: &str) -> Option<String> {
+    let rest = url.trim().strip_prefix("https://").or_else(|| url.trim().strip_prefix("http://"))?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    let host = host.rsplit('@').next()?.split(':').next()?.to_lowercase();
+    if host.is_empty() || !host.contains('.') {
+        return None;
+    }
+    Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+}
+
+/// 按常见网站的标题后缀推断域名
+pub fn domain_from_title(title: &str) -> Option<String> {
+    TITLE_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| title.contains(suffix))
+        .map(|(_, domain)| domain.to_string())
+}
+
+/// 推断浏览器窗口当前标签页的域名：优先用扩展上报（标签标题出现在窗口标题里才算同一页），否则解析标题
+pub fn domain_for(title: &str, exe: &str) -> Option<String> {
+    if !is_browser(exe) {
+        return None;
+    }
+    if let Some(tab) = LATEST_TAB.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        if tab.at.elapsed() < TAB_FRESH && !tab.title.is_empty() && title.contains(&tab.title) {
+            return Some(tab.domain.clone());
+        }
+    }
+    domain_from_title(title)
+}
+
+#[derive(Deserialize)]
+struct TabReport {
+    url: String,
+    #[serde(default)]
+    title: String,
+}
+
+/// 在 127.0.0.1:port 上启动标签页上报端点，浏览器扩展 POST /tab {"url", "title"}
+///
+/// 要求 Content-Type 为 application/json，普通网页的跨域简单请求发不过来
+pub fn start_bridge(port: u16) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("标签页上报端口 {} 启动失败: {}", port, e))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+            let status = match handle(&stream) {
+                Ok(()) => "204 No Content",
+                Err(status) => status,
+            };
+            let mut stream = stream;
+            let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+        }
+    });
+    Ok(())
+}
+
+fn handle(stream: &TcpStream) -> Result<(), &'static str> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|_| "400 Bad Request")?;
+    if !line.starts_with("POST /tab ") {
+        return Err("404 Not Found");
+    }
+
+    let mut len = 0usize;
+    let mut json = false;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|_| "400 Bad Request")?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.trim().to_lowercase().as_str() {
+                "content-length" => len = value.trim().parse().unwrap_or(0),
+                "content-type" => json = value.trim().starts_with("application/json"),
+                _ => {}
+            }
+        }
+    }
+    if !json {
+        return Err("415 Unsupported Media Type");
+    }
+    if len == 0 || len > 64 * 1024 {
+        return Err("400 Bad Request");
+    }
+
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).map_err(|_| "400 Bad Request")?;
+    let report: TabReport = serde_json::from_slice(&body).map_err(|_| "400 Bad Request")?;
+    let tab = host_of(&report.url).map(|domain| Tab { domain, title: report.title, at: Instant::now() });
+    // 非 http(s) 页面（新标签页、设置页）清空上报，退回标题解析
+    *LATEST_TAB.lock().unwrap_or_else(|e| e.into_inner()) = tab;
+    Ok(())
+}
@@ -0,0 +1,460 @@
+use crate::tracker::{self, ActivityEntry};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// 未命中任何规则时的分类
+pub const FALLBACK_CATEGORY: &str = "其他";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryDef {
+    pub name: String,
+    #[serde(default)]
+    pub color: String,
+}
+
+/// 分类规则：exe 精确匹配（忽略大小写和 .exe 后缀）、标题正则、网页域名（含子域名），写了的条件都要满足
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryRule {
+    pub id: String,
+    pub category: String,
+    #[serde(default)]
+    pub exe: Vec<String>,
+    #[serde(default)]
+    pub title_regex: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domain: Vec<String>,
+    /// 优先级高的先匹配，同优先级按文件中的顺序
+    #[serde(default)]
+    pub priority: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryConfig {
+    #[serde(default)]
+    pub categories: Vec<CategoryDef>,
+    #[serde(default)]
+    pub rules: Vec<CategoryRule>,
+}
+
+/// 预览结果：命中的规则（未命中时 rule_id 为空）
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryMatch {
+    pub category: String,
+    pub rule_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReclassifyReport {
+    pub files: u32,
+    pub entries: u32,
+    pub changed: u32,
+}
+
+struct CompiledRule {
+    index: usize,
+    /// 已规范化的 exe 列表
+    exes: Vec<String>,
+    domains: Vec<String>,
+    regex: Option<Regex>,
+}
+
+/// 编译后的规则集
+pub struct RuleSet {
+    pub config: CategoryConfig,
+    /// 按优先级排好序的规则
+    compiled: Vec<CompiledRule>,
+}
+
+impl RuleSet {
+    fn compile(config: CategoryConfig) -> Result<Self, String> {
+        let mut compiled = Vec::new();
+        for (i, rule) in config.rules.iter().enumerate() {
+            let regex = match &rule.title_regex {
+                Some(pattern) if !pattern.is_empty() => Some(
+                    Regex::new(pattern).map_err(|e| format!("规则 {} 的正则无效: {}", rule.id, e))?,
+                ),
+                _ => None,
+            };
+            let exes: Vec<String> = rule.exe.iter().map(|e| normalize_exe(e)).filter(|e| !e.is_empty()).collect();
+            let domains: Vec<String> = rule
+                .domain
+                .iter()
+                .map(|d| d.trim().trim_start_matches("www.").to_lowercase())
+                .filter(|d| !d.is_empty())
+                .collect();
+            if exes.is_empty() && domains.is_empty() && regex.is_none() {
+                return Err(format!("规则 {} 至少需要 exe、title_regex 或 domain 之一", rule.id));
+            }
+            compiled.push(CompiledRule { index: i, exes, domains, regex });
+        }
+        // sort_by_key 是稳定排序，同优先级保持文件顺序
+        compiled.sort_by_key(|r| -config.rules[r.index].priority);
+        Ok(Self { config, compiled })
+    }
+
+    /// 返回命中的规则
+    pub fn matched(&self, exe: &str, title: &str, domain: Option<&str>) -> Option<&CategoryRule> {
+        let exe = normalize_exe(exe);
+        for rule in &self.compiled {
+            if !rule.exes.is_empty() && !rule.exes.contains(&exe) {
+                continue;
+            }
+            if !rule.domains.is_empty() {
+                let Some(domain) = domain else { continue };
+                let hit = rule.domains.iter().any(|d| domain == d || domain.ends_with(&format!(".{}", d)));
+                if !hit {
+                    continue;
+                }
+            }
+            if let Some(re) = &rule.regex {
+                if !re.is_match(title) {
+                    continue;
+                }
+            }
+            return Some(&self.config.rules[rule.index]);
+        }
+        None
+    }
+
+    pub fn categorize(&self, exe: &str, title: &str, domain: Option<&str>) -> String {
+        self.matched(exe, title, domain)
+            .map(|r| r.category.clone())
+            .unwrap_or_else(|| FALLBACK_CATEGORY.to_string())
+    }
+
+    pub fn categorize_entry(&self, e: &ActivityEntry) -> String {
+        self.categorize(&e.exe, &e.title, e.domain.as_deref())
+    }
+
+    /// 所有分类名（声明的分类 + 规则里出现的分类），保持首次出现的顺序
+    pub fn category_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        let declared = self.config.categories.iter().map(|c| &c.name);
+        let used = self.config.rules.iter().map(|r| &r.category);
+        for name in declared.chain(used) {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+}
+
+fn normalize_exe(exe: &str) -> String {
+    let lower = exe.trim().to_lowercase();
+    lower.strip_suffix(".exe").map(|s| s.to_string()).unwrap_or(lower)
+}
+
+// === 默认规则（首次运行时写入 categories.json） ===
+
+const DEFAULT_RULES: &[(&str, &str, &[&str], &[&str])] = &[
+    // (分类, 颜色, exe, 标题关键词)
+    ("编程", "#007AFF", &[
+        "code", "code - insiders", "devenv", "pycharm64", "pycharm", "idea64", "idea",
+        "webstorm64", "webstorm", "vim", "gvim", "nvim", "sublime_text", "atom", "cursor",
+        "windsurf", "windowsterminal", "wt", "powershell", "pwsh", "cmd", "alacritty",
+        "kitty", "konsole", "gnome-terminal-server", "wezterm-gui", "git", "node", "python",
+        "java", "claude",
+    ], &["visual studio", "pycharm", "intellij idea", "webstorm", "neovim", "devtools", "terminal", "powershell"]),
+    ("浏览器", "#FF9500", &["chrome", "firefox", "msedge", "brave", "opera", "safari", "chromium"], &[]),
+    ("游戏", "#FF3B30", &[
+        "steam", "steamwebhelper", "epicgameslauncher", "genshinimpact", "yuanshen",
+        "minecraft", "leagueclient", "league of legends", "valorant",
+    ], &["原神", "崩坏", "minecraft", "genshin impact"]),
+    ("视频", "#AF52DE", &["vlc", "mpv", "potplayer", "potplayermini64", "哔哩哔哩"], &["youtube", "bilibili", "哔哩哔哩"]),
+    ("社交", "#34C759", &["wechat", "weixin", "telegram", "discord", "qq", "slack", "teams", "ms-teams"], &["wechat", "telegram", "discord", "slack"]),
+    ("文档", "#5AC8FA", &[
+        "winword", "excel", "powerpnt", "wps", "notion", "obsidian", "typora", "notepad",
+        "notepad++", "onenote", "soffice.bin",
+    ], &["word", "excel", "powerpoint", "notion", "obsidian", "onenote"]),
+    ("音乐", "#FF2D55", &["spotify", "cloudmusic", "qqmusic"], &["spotify", "网易云"]),
+];
+
+const DEFAULT_DOMAINS: &[(&str, &[&str])] = &[
+    ("编程", &["github.com", "gitlab.com", "stackoverflow.com", "docs.rs", "crates.io", "developer.mozilla.org", "npmjs.com"]),
+    ("视频", &["youtube.com", "bilibili.com", "netflix.com", "iqiyi.com", "youku.com", "v.qq.com"]),
+    ("社交", &["x.com", "twitter.com", "weibo.com", "reddit.com", "web.whatsapp.com", "discord.com"]),
+    ("文档", &["docs.google.com", "notion.so", "feishu.cn", "yuque.com"]),
+    ("音乐", &["music.163.com", "open.spotify.com", "y.qq.com"]),
+    ("游戏", &["store.steampowered.com", "twitch.tv"]),
+];
+
+/// 标题关键词转正则：ASCII 关键词加单词边界，避免 "code" 命中 "barcode"
+fn keywords_regex(keywords: &[&str]) -> String {
+    let parts: Vec<String> = keywords
+        .iter()
+        .map(|kw| {
+            if kw.is_ascii() {
+                format!(r"\b{}\b", regex::escape(kw))
+            } else {
+                regex::escape(kw)
+            }
+        })
+        .collect();
+    format!("(?i)(?:{})", parts.join("|"))
+}
+
+pub fn default_config() -> CategoryConfig {
+    let mut categories = Vec::new();
+    let mut rules = Vec::new();
+    for (cat, color, exes, keywords) in DEFAULT_RULES {
+        categories.push(CategoryDef { name: cat.to_string(), color: color.to_string() });
+        // 进程名比标题可靠，优先匹配
+        rules.push(CategoryRule {
+            id: format!("{}-exe", cat),
+            category: cat.to_string(),
+            exe: exes.iter().map(|e| e.to_string()).collect(),
+            title_regex: None,
+            domain: vec![],
+            priority: 10,
+        });
+        if !keywords.is_empty() {
+            rules.push(CategoryRule {
+                id: format!("{}-title", cat),
+                category: cat.to_string(),
+                exe: vec![],
+                title_regex: Some(keywords_regex(keywords)),
+                domain: vec![],
+                priority: 0,
+            });
+        }
+    }
+    // 网页域名最具体，浏览器里的时间按网站归类
+    for (cat, domains) in DEFAULT_DOMAINS {
+        rules.push(CategoryRule {
+            id: format!("{}-domain", cat),
+            category: cat.to_string(),
+            exe: vec![],
+            title_regex: None,
+            domain: domains.iter().map(|d| d.to_string()).collect(),
+            priority: 20,
+        });
+    }
+    categories.push(CategoryDef { name: FALLBACK_CATEGORY.to_string(), color: "#8E8E93".to_string() });
+    CategoryConfig { categories, rules }
+}
+
+// === 读写与缓存 ===
+
+fn rules_path(data_dir: &PathBuf) -> PathBuf {
+    data_dir.join("categories.json")
+}
+
+/// 每个 categories.json 的修改时间、可用的规则集和解析错误，文件变化时重新编译
+struct CachedRules {
+    mtime: Option<SystemTime>,
+    /// 文件有效时就是它的规则，解析失败时保留上一次有效的规则（没有时用默认规则）
+    rules: Arc<RuleSet>,
+    error: Option<String>,
+}
+
+static CACHE: Mutex<Option<HashMap<PathBuf, CachedRules>>> = Mutex::new(None);
+
+/// 内置默认规则，编译结果由测试保证有效
+fn default_rules() -> Arc<RuleSet> {
+    static DEFAULT: OnceLock<Arc<RuleSet>> = OnceLock::new();
+    DEFAULT
+        .get_or_init(|| Arc::new(RuleSet::compile(default_config()).expect("默认分类规则无效")))
+        .clone()
+}
+
+fn read_rules(path: &PathBuf) -> Result<RuleSet, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("读取 categories.json 失败: {}", e))?;
+    let config: CategoryConfig =
+        serde_json::from_str(&content).map_err(|e| format!("categories.json 解析失败: {}", e))?;
+    RuleSet::compile(config).map_err(|e| format!("categories.json 无效: {}", e))
+}
+
+/// 读取规则集和当前的解析错误；categories.json 不存在时写入默认规则
+fn load_cached(data_dir: &PathBuf) -> (Arc<RuleSet>, Option<String>) {
+    let path = rules_path(data_dir);
+    if !path.exists() {
+        let _ = save_config(data_dir, &default_config());
+    }
+    let mtime = fs::metadata(&path).and_then(|m| m.modified()).ok();
+
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if let Some(cached) = cache.get(&path) {
+        if cached.mtime == mtime && mtime.is_some() {
+            return (cached.rules.clone(), cached.error.clone());
+        }
+    }
+
+    let (rules, error) = match read_rules(&path) {
+        Ok(rules) => (Arc::new(rules), None),
+        Err(e) => {
+            let last_good = cache.get(&path).map(|c| c.rules.clone()).unwrap_or_else(default_rules);
+            (last_good, Some(e))
+        }
+    };
+    cache.insert(path, CachedRules { mtime, rules: rules.clone(), error: error.clone() });
+    (rules, error)
+}
+
+/// 加载规则集，供追踪和统计使用；categories.json 改坏时继续用上一次有效的规则（启动时就坏则用默认规则），
+/// 错误由 try_load 返回
+pub fn load(data_dir: &PathBuf) -> Arc<RuleSet> {
+    load_cached(data_dir).0
+}
+
+/// 加载 categories.json 中的规则，解析失败或正则无效时返回错误；会改写数据或展示给用户的地方用它
+pub fn try_load(data_dir: &PathBuf) -> Result<Arc<RuleSet>, String> {
+    match load_cached(data_dir) {
+        (rules, None) => Ok(rules),
+        (_, Some(e)) => Err(e),
+    }
+}
+
+/// 校验并保存规则
+pub fn save_config(data_dir: &PathBuf, config: &CategoryConfig) -> Result<(), String> {
+    RuleSet::compile(config.clone())?;
+    fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    fs::write(rules_path(data_dir), json).map_err(|e| e.to_string())
+}
+
+/// 按当前规则给一条记录分类
+pub fn categorize(data_dir: &PathBuf, exe: &str, title: &str, domain: Option<&str>) -> String {
+    load(data_dir).categorize(exe, title, domain)
+}
+
+/// 预览一条记录会命中哪条规则
+pub fn preview(data_dir: &PathBuf, entry: &ActivityEntry) -> CategoryMatch {
+    let rules = load(data_dir);
+    match rules.matched(&entry.exe, &entry.title, entry.domain.as_deref()) {
+        Some(rule) => CategoryMatch { category: rule.category.clone(), rule_id: Some(rule.id.clone()) },
+        None => CategoryMatch { category: FALLBACK_CATEGORY.to_string(), rule_id: None },
+    }
+}
+
+/// 用当前规则重写所有历史活动记录的 category 字段
+pub fn reclassify(data_dir: &PathBuf) -> Result<ReclassifyReport, String> {
+    // 规则文件有误时不能拿旧规则或默认规则重写全部历史记录
+    let rules = try_load(data_dir)?;
+    let dir = data_dir.join("activity");
+    let mut report = ReclassifyReport { files: 0, entries: 0, changed: 0 };
+    if !dir.exists() {
+        return Ok(report);
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().map(|x| x == "jsonl").unwrap_or(false))
+        .collect();
+    files.sort();
+
+    // 与追踪器的追加写互斥，避免重写期间丢记录
+    let _guard = tracker::ACTIVITY_WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    for path in files {
+        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let mut lines = Vec::new();
+        let mut changed = 0u32;
+        for line in content.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<ActivityEntry>(trimmed) {
+                Ok(mut entry) => {
+                    report.entries += 1;
+                    let category = if entry.afk { None } else { Some(rules.categorize_entry(&entry)) };
+                    if entry.category != category {
+                        entry.category = category;
+                        changed += 1;
+                    }
+                    lines.push(serde_json::to_string(&entry).map_err(|e| e.to_string())?);
+                }
+                // 解析不了的行原样保留
+                Err(_) => lines.push(trimmed.to_string()),
+            }
+        }
+        report.files += 1;
+        if changed > 0 {
+            let tmp = path.with_extension("jsonl.tmp");
+            fs::write(&tmp, lines.join("\n") + "\n").map_err(|e| e.to_string())?;
+            fs::rename(&tmp, &path).map_err(|e| e.to_string())?;
+            report.changed += changed;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xiaolanniao-categories-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// 写入后把修改时间往后拨，避免和上一次写入落在同一时间戳而命中缓存
+    fn write_rules(dir: &PathBuf, content: &str, secs: u64) {
+        let path = rules_path(dir);
+        fs::write(&path, content).unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000 + secs);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(mtime).unwrap();
+    }
+
+    fn write_day(dir: &PathBuf, entry: &ActivityEntry) -> String {
+        let path = dir.join("activity").join("2026-03-01.jsonl");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let line = serde_json::to_string(entry).unwrap() + "\n";
+        fs::write(&path, &line).unwrap();
+        line
+    }
+
+    #[test]
+    fn default_rules_compile() {
+        let rules = RuleSet::compile(default_config()).unwrap();
+        assert_eq!(rules.categorize("Code.exe", "main.rs", None), "编程");
+        assert_eq!(rules.categorize("chrome.exe", "Rust", Some("docs.rs")), "编程");
+        assert_eq!(default_rules().categorize("unknown", "", None), FALLBACK_CATEGORY);
+    }
+
+    #[test]
+    fn broken_file_is_reported_and_last_good_rules_kept() {
+        let dir = temp_dir("broken");
+        let custom = r#"{"rules":[{"id":"r","category":"自定义","exe":["code"]}]}"#;
+        write_rules(&dir, custom, 0);
+        assert_eq!(try_load(&dir).unwrap().categorize("code", "", None), "自定义");
+
+        write_rules(&dir, r#"{"rules":[{"id":"r","category":"自定义","title_regex":"("}]}"#, 1);
+        assert!(try_load(&dir).err().unwrap().contains("正则无效"));
+        assert_eq!(load(&dir).categorize("code", "", None), "自定义");
+
+        write_rules(&dir, "{ not json", 2);
+        assert!(try_load(&dir).err().unwrap().contains("解析失败"));
+        assert_eq!(load(&dir).categorize("code", "", None), "自定义");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reclassify_refuses_broken_rules() {
+        let dir = temp_dir("reclassify");
+        write_rules(&dir, "{ not json", 0);
+        let entry = ActivityEntry {
+            ts: "2026-03-01T09:00:00".to_string(),
+            title: "main.rs".to_string(),
+            exe: "code.exe".to_string(),
+            duration: 60,
+            afk: false,
+            category: Some("自定义".to_string()),
+            domain: None,
+        };
+        let before = write_day(&dir, &entry);
+        assert!(reclassify(&dir).is_err());
+        assert_eq!(fs::read_to_string(dir.join("activity").join("2026-03-01.jsonl")).unwrap(), before);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
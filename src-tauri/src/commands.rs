@@ -2,6 +2,7 @@ use crate::config::{self, AppConfig};
 use crate::tracker::Tracker;
 use crate::tray_icon;
 use crate::activity;
+use crate::categories;
 use crate::journal;
 use crate::claude_api;
 use crate::memes;
@@ -14,11 +15,17 @@ use crate::vocab;
 use crate::inspiration;
 use crate::writing;
 use crate::ai_provider;
+use crate::ai_client::{self, Feature};
+use crate::ai_usage;
+use crate::ai_history;
+use crate::prompts;
 use crate::dream;
+use crate::edits;
+use crate::transfer;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Mutex;
-use tauri::{AppHandle, Emitter, State, image::Image};
+use tauri::{AppHandle, Emitter, Manager, State, image::Image};
 
 pub struct TrackerState(pub Mutex<Tracker>);
 pub struct TrayState(pub Mutex<Option<tauri::tray::TrayIcon>>);
@@ -28,13 +35,57 @@ pub fn get_config() -> Result<AppConfig, String> {
     Ok(config::load_config())
 }
 
+/// config.json 无法解析时的错误信息，此时界面显示的是默认配置
 #[tauri::command]
-pub fn save_config(cfg: AppConfig) -> Result<(), String> {
-    config::save_config_to_file(&cfg)
+pub fn get_config_error() -> Option<String> {
+    config::load_error()
 }
 
+/// 启动时失败但不影响运行的步骤（标签页上报、开机启动项、密钥迁移、自动监测），界面就绪后由设置页取出显示
+static STARTUP_WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn add_startup_warning(msg: String) {
+    STARTUP_WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).push(msg);
+}
+
+#[tauri::command]
+pub fn get_startup_warnings() -> Vec<String> {
+    STARTUP_WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[tauri::command]
+pub fn save_config(mut cfg: AppConfig, app: AppHandle) -> Result<(), String> {
+    crate::privacy::validate(&cfg.privacy)?;
+    crate::secrets::seal_config(&mut cfg)?;
+    if cfg.launch_on_login != config::load_config().launch_on_login {
+        crate::autostart::set_launch_on_login(cfg.launch_on_login)?;
+    }
+    config::save_config_to_file(&cfg)?;
+    apply_config(&app, &config::load_config());
+    Ok(())
+}
+
+/// 配置变化后通知各子系统：追踪器立即换用新的间隔、空闲阈值和隐私规则，前端收到 config-changed
+pub fn apply_config(app: &AppHandle, cfg: &AppConfig) {
+    if let Ok(tracker) = app.state::<TrackerState>().0.lock() {
+        tracker.reconfigure(cfg.interval, cfg.idle_threshold_secs, crate::privacy::Privacy::new(&cfg.privacy));
+    }
+    let _ = app.emit("config-changed", cfg);
+}
+
+/// 用口令解锁加密密钥文件，并迁移之前因未解锁而留在 config.json 里的明文密钥
 #[tauri::command]
-pub fn start_monitor(state: State<TrackerState>, tray: State<TrayState>, app: AppHandle) -> Result<(), String> {
+pub fn unlock_secrets(passphrase: String) -> Result<(), String> {
+    crate::secrets::unlock(&passphrase)?;
+    crate::secrets::migrate_plaintext()
+}
+
+/// 启动追踪并把托盘切换为绿色鸟，供 start_monitor 和启动时自动监测共用
+pub fn start_tracking(app: &AppHandle) -> Result<(), String> {
+    if crate::relocate::MOVING.load(std::sync::atomic::Ordering::SeqCst) {
+        return Err("正在迁移数据目录，请稍后再开始监测".into());
+    }
+    let state = app.state::<TrackerState>();
     let mut tracker = state.0.lock().map_err(|e| e.to_string())?;
     let handle = app.clone();
     tracker.start(move |entry| {
@@ -42,6 +93,7 @@ pub fn start_monitor(state: State<TrackerState>, tray: State<TrayState>, app: Ap
     });
 
     // 切换为绿色鸟
+    let tray = app.state::<TrayState>();
     if let Some(ref tray_icon) = *tray.0.lock().unwrap() {
         let green = tray_icon::green_bird();
         let _ = tray_icon.set_icon(Some(Image::new_owned(green, 64, 64)));
@@ -50,6 +102,11 @@ pub fn start_monitor(state: State<TrackerState>, tray: State<TrayState>, app: Ap
     Ok(())
 }
 
+#[tauri::command]
+pub fn start_monitor(app: AppHandle) -> Result<(), String> {
+    start_tracking(&app)
+}
+
 #[tauri::command]
 pub fn stop_monitor(state: State<TrackerState>, tray: State<TrayState>) -> Result<(), String> {
     let mut tracker = state.0.lock().map_err(|e| e.to_string())?;
@@ -70,6 +127,53 @@ pub fn get_monitor_status(state: State<TrackerState>) -> Result<bool, String> {
     Ok(tracker.is_running())
 }
 
+/// 把整个数据目录迁移到 new_path：复制、逐个校验，成功后才改配置并删除旧目录，进度通过 data-dir-progress 事件推送
+#[tauri::command]
+pub async fn move_data_dir(new_path: String, app: AppHandle) -> Result<crate::relocate::MoveReport, String> {
+    use crate::relocate::{self, MOVING};
+    use std::sync::atomic::Ordering;
+
+    let new_path = new_path.trim().to_string();
+    if new_path.is_empty() {
+        return Err("请选择新的数据目录".into());
+    }
+    if MOVING.swap(true, Ordering::SeqCst) {
+        return Err("数据目录正在迁移中".into());
+    }
+    let running = app.state::<TrackerState>().0.lock().map(|t| t.is_running()).unwrap_or(true);
+    if running {
+        MOVING.store(false, Ordering::SeqCst);
+        return Err("请先停止监测再迁移数据目录".into());
+    }
+
+    let handle = app.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let progress = |p: relocate::MoveProgress| {
+            let _ = handle.emit("data-dir-progress", &p);
+        };
+        let mut cfg = config::load_config();
+        let from = PathBuf::from(&cfg.data_dir);
+        let to = PathBuf::from(&new_path);
+        let mut report = relocate::copy_tree(&from, &to, progress)?;
+
+        cfg.data_dir = new_path;
+        if let Err(e) = config::save_config_to_file(&cfg) {
+            relocate::undo_copy(&to, &report);
+            return Err(format!("保存配置失败，已撤销迁移: {}", e));
+        }
+        if let Ok(mut tracker) = handle.state::<TrackerState>().0.lock() {
+            tracker.set_data_dir(to.clone());
+        }
+        apply_config(&handle, &cfg);
+        relocate::remove_old(&from, &to, &mut report, progress);
+        Ok(report)
+    })
+    .await
+    .map_err(|e| e.to_string());
+    MOVING.store(false, Ordering::SeqCst);
+    result?
+}
+
 fn get_data_dir() -> PathBuf {
     let cfg = config::load_config();
     PathBuf::from(&cfg.data_dir)
@@ -88,11 +192,50 @@ pub fn load_activity(date: String) -> Result<Vec<crate::tracker::ActivityEntry>,
 
 #[tauri::command]
 pub fn get_activity_summary(date: String) -> Result<HashMap<String, u64>, String> {
-    let entries = activity::load_entries(&get_data_dir(), &date);
-    let summary = activity::summarize(&entries);
+    let data_dir = get_data_dir();
+    let entries = activity::load_entries(&data_dir, &date);
+    let summary = activity::summarize(&data_dir, &entries);
     Ok(summary.into_iter().collect())
 }
 
+// === 手动修改活动记录 ===
+
+/// 补录一条离线活动，返回修改后的当天记录
+#[tauri::command]
+pub fn insert_activity(entry: crate::tracker::ActivityEntry) -> Result<Vec<crate::tracker::ActivityEntry>, String> {
+    edits::add(&get_data_dir(), edits::EditOp::Insert { entry })
+}
+
+#[tauri::command]
+pub fn split_activity(ts: String, exe: String, at: String) -> Result<Vec<crate::tracker::ActivityEntry>, String> {
+    edits::add(&get_data_dir(), edits::EditOp::Split { ts, exe, at })
+}
+
+#[tauri::command]
+pub fn set_activity_category(
+    ts: String,
+    exe: String,
+    category: Option<String>,
+) -> Result<Vec<crate::tracker::ActivityEntry>, String> {
+    let category = category.filter(|c| !c.trim().is_empty());
+    edits::add(&get_data_dir(), edits::EditOp::SetCategory { ts, exe, category })
+}
+
+#[tauri::command]
+pub fn delete_activity(ts: String, exe: String) -> Result<Vec<crate::tracker::ActivityEntry>, String> {
+    edits::add(&get_data_dir(), edits::EditOp::Delete { ts, exe })
+}
+
+#[tauri::command]
+pub fn list_activity_edits(date: String) -> Result<Vec<edits::ActivityEdit>, String> {
+    Ok(edits::load(&get_data_dir(), &date))
+}
+
+#[tauri::command]
+pub fn undo_activity_edit(date: String, id: u64) -> Result<Vec<crate::tracker::ActivityEntry>, String> {
+    edits::undo(&get_data_dir(), &date, id)
+}
+
 #[tauri::command]
 pub fn get_daily_totals(days: usize) -> Result<Vec<(String, u64)>, String> {
     Ok(activity::daily_totals(&get_data_dir(), days))
@@ -103,6 +246,79 @@ pub fn get_range_summary(days: usize) -> Result<Vec<(String, u64)>, String> {
     Ok(activity::range_summary(&get_data_dir(), days))
 }
 
+#[tauri::command]
+pub fn get_daily_totals_between(from: String, to: String) -> Result<Vec<(String, u64)>, String> {
+    let period = activity::ReportPeriod::parse(&format!("{}..{}", from, to))?;
+    Ok(activity::daily_totals_between(&get_data_dir(), period.from, period.to))
+}
+
+#[tauri::command]
+pub fn get_range_summary_between(from: String, to: String) -> Result<Vec<(String, u64)>, String> {
+    let period = activity::ReportPeriod::parse(&format!("{}..{}", from, to))?;
+    Ok(activity::range_summary_between(&get_data_dir(), period.from, period.to))
+}
+
+/// 解析时间段，供前端展示自然周/自然月的起止日期
+#[tauri::command]
+pub fn resolve_period(period: String) -> Result<activity::ReportPeriod, String> {
+    activity::ReportPeriod::parse(&period)
+}
+
+#[tauri::command]
+pub fn get_activity_heatmap(
+    from: String,
+    to: String,
+    category: Option<String>,
+    exe: Option<String>,
+    by_date: Option<bool>,
+) -> Result<activity::Heatmap, String> {
+    let from = activity::parse_date(&from)?;
+    let to = activity::parse_date(&to)?;
+    if from > to {
+        return Err("开始日期不能晚于结束日期".into());
+    }
+    activity::heatmap(
+        &get_data_dir(),
+        from,
+        to,
+        category.as_deref().filter(|s| !s.is_empty()),
+        exe.as_deref().filter(|s| !s.is_empty()),
+        by_date.unwrap_or(false),
+    )
+}
+
+// === 分类规则 ===
+
+#[tauri::command]
+pub fn get_category_rules() -> Result<categories::CategoryConfig, String> {
+    Ok(categories::try_load(&get_data_dir())?.config.clone())
+}
+
+/// categories.json 解析失败时拒绝保存，避免覆盖手写的规则；可先修正文件或重置为默认规则
+#[tauri::command]
+pub fn save_category_rules(rules: categories::CategoryConfig) -> Result<(), String> {
+    let data_dir = get_data_dir();
+    categories::try_load(&data_dir)?;
+    categories::save_config(&data_dir, &rules)
+}
+
+#[tauri::command]
+pub fn reset_category_rules() -> Result<categories::CategoryConfig, String> {
+    let rules = categories::default_config();
+    categories::save_config(&get_data_dir(), &rules)?;
+    Ok(rules)
+}
+
+#[tauri::command]
+pub fn preview_category(entry: crate::tracker::ActivityEntry) -> Result<categories::CategoryMatch, String> {
+    Ok(categories::preview(&get_data_dir(), &entry))
+}
+
+#[tauri::command]
+pub fn reclassify_activity() -> Result<categories::ReclassifyReport, String> {
+    categories::reclassify(&get_data_dir())
+}
+
 #[tauri::command]
 pub fn load_journal(date: String) -> Result<String, String> {
     Ok(journal::load(&get_data_dir(), &date))
@@ -132,32 +348,34 @@ pub fn search_journals(keyword: String) -> Result<Vec<(String, String)>, String>
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn ai_generate(date: String, existing_text: String, app: AppHandle) -> Result<String, String> {
+pub async fn ai_generate(date: String, existing_text: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
     let cfg = config::load_config();
-    if cfg.api_key.is_empty() {
-        return Err("请先在设置中填写 API Key".to_string());
-    }
     let data_dir = PathBuf::from(&cfg.data_dir);
-    claude_api::generate(&app, &cfg.api_key, &cfg.model, &cfg.language, &cfg.personality, &date, &existing_text, &data_dir).await
+    claude_api::generate(&app, request_id, &cfg.language, &cfg.personality, &date, &existing_text, &data_dir).await
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn ai_organize(date: String, raw_content: String, app: AppHandle) -> Result<String, String> {
+pub async fn ai_organize(date: String, raw_content: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
     let cfg = config::load_config();
-    if cfg.api_key.is_empty() {
-        return Err("请先在设置中填写 API Key".to_string());
-    }
-    claude_api::organize(&app, &cfg.api_key, &cfg.model, &cfg.language, &cfg.personality, &date, &raw_content).await
+    let data_dir = PathBuf::from(&cfg.data_dir);
+    claude_api::organize(&app, request_id, &cfg.language, &cfg.personality, &date, &raw_content, &data_dir).await
 }
 
-#[tauri::command]
-pub async fn ai_report(days: usize, app: AppHandle) -> Result<String, String> {
+#[tauri::command(rename_all = "snake_case")]
+pub async fn ai_report(days: usize, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
     let cfg = config::load_config();
-    if cfg.api_key.is_empty() {
-        return Err("请先在设置中填写 API Key".to_string());
-    }
     let data_dir = PathBuf::from(&cfg.data_dir);
-    claude_api::report(&app, &cfg.api_key, &cfg.model, &cfg.language, &cfg.personality, days, &data_dir).await
+    let period = activity::ReportPeriod::last_days(days);
+    claude_api::report(&app, request_id, &cfg.language, &cfg.personality, &period, &data_dir).await
+}
+
+/// 按自然周/自然月/日期区间生成报告，period 如 2026-W10、2026-03、2026-03-01..2026-03-15
+#[tauri::command(rename_all = "snake_case")]
+pub async fn ai_report_period(period: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
+    let cfg = config::load_config();
+    let data_dir = PathBuf::from(&cfg.data_dir);
+    let period = activity::ReportPeriod::parse(&period)?;
+    claude_api::report(&app, request_id, &cfg.language, &cfg.personality, &period, &data_dir).await
 }
 
 #[tauri::command]
@@ -190,6 +408,46 @@ pub async fn browse_directory(app: AppHandle) -> Result<Option<String>, String>
     rx.await.map_err(|e| e.to_string())
 }
 
+/// 导出 [from, to] 的活动记录，弹出保存对话框；取消时返回 None
+#[tauri::command]
+pub async fn export_activity(from: String, to: String, format: String, app: AppHandle) -> Result<Option<u32>, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let period = activity::ReportPeriod::parse(&format!("{}..{}", from, to))?;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title("导出活动记录")
+        .set_file_name(format!("activity_{}_{}.{}", from, to, format))
+        .add_filter(&format.to_uppercase(), &[format.as_str()])
+        .save_file(move |path| {
+            let _ = tx.send(path.and_then(|p| p.as_path().map(|p| p.to_path_buf())));
+        });
+    let path = match rx.await.map_err(|e| e.to_string())? {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    transfer::export(&get_data_dir(), period.from, period.to, &format, &path).map(Some)
+}
+
+/// 从 ActivityWatch（JSON）或 RescueTime（CSV）导入活动记录；取消时返回 None
+#[tauri::command]
+pub async fn import_activity(source: Option<String>, app: AppHandle) -> Result<Option<transfer::ImportReport>, String> {
+    use tauri_plugin_dialog::DialogExt;
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title("导入活动记录")
+        .add_filter("ActivityWatch / RescueTime", &["json", "csv"])
+        .pick_file(move |path| {
+            let _ = tx.send(path.and_then(|p| p.as_path().map(|p| p.to_path_buf())));
+        });
+    let path = match rx.await.map_err(|e| e.to_string())? {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    transfer::import(&get_data_dir(), &path, source.as_deref()).map(Some)
+}
+
 #[tauri::command]
 pub fn get_chick_mood() -> Result<mood::ChickMood, String> {
     Ok(mood::evaluate(&get_data_dir()))
@@ -265,50 +523,41 @@ pub fn save_theater(entry: theater::TheaterEntry) -> Result<(), String> {
     theater::save(&get_data_dir(), &entry)
 }
 
-#[tauri::command(rename_all = "snake_case")]
-pub async fn generate_theater(date: String, app: AppHandle) -> Result<String, String> {
-    let cfg = config::load_config();
-    if cfg.api_key.is_empty() {
-        return Err("请先在设置中填写 API Key".to_string());
-    }
-    let data_dir = PathBuf::from(&cfg.data_dir);
-
+/// 小剧场模板的变量
+fn theater_vars(cfg: &AppConfig, data_dir: &PathBuf, date: &str) -> prompts::Vars {
     // 收集当天数据
-    let entries = activity::load_entries(&data_dir, &date);
-    let summary = activity::summarize(&entries);
-    let journal_text = journal::load(&data_dir, &date);
-
-    let mut user_msg = format!("请为 {} 生成一篇轻小说风格的「一日番外」。\n\n", date);
+    let entries = activity::load_entries(data_dir, date);
+    let summary = activity::summarize(data_dir, &entries);
+    let journal_text = journal::load(data_dir, date);
 
+    let mut activity_text = String::new();
     if !summary.is_empty() {
-        user_msg.push_str("今日活动：\n");
+        activity_text.push_str("今日活动：\n");
         for (cat, sec) in &summary {
-            user_msg.push_str(&format!("- {}: {}分钟\n", cat, sec / 60));
+            activity_text.push_str(&format!("- {}: {}分钟\n", cat, sec / 60));
         }
-        user_msg.push('\n');
+        activity_text.push('\n');
     }
+    let mut journal_snippet = String::new();
     if !journal_text.trim().is_empty() {
         let snippet: String = journal_text.chars().take(500).collect();
-        user_msg.push_str(&format!("今日日记摘要：\n{}\n\n", snippet));
+        journal_snippet = format!("今日日记摘要：\n{}\n\n", snippet);
     }
 
-    let persona = crate::personality::get(&cfg.personality);
-    let system_prompt = format!(
-        "{}\n{}",
-        persona.ai_persona,
-        "你是一个轻小说风格的叙事者。根据用户的一天活动和日记，生成一篇有趣的「一日番外」。\n\
-         要求：\n\
-         - 第三人称视角，主角是「主人」\n\
-         - 有场景描写、事件推进、情绪旁白\n\
-         - 语气轻松有趣，像轻小说\n\
-         - 300-500字正文\n\
-         - 正文结束后，用 --- 分隔，给出2-3个「如果」分支，格式：\n\
-           ## 如果xxx\n\
-           （50-100字的平行世界短文）\n\
-         - 用中文"
-    );
+    prompts::vars([
+        ("persona", crate::personality::get(&cfg.personality).ai_persona.to_string()),
+        ("date", date.to_string()),
+        ("activity_summary", activity_text),
+        ("journal_snippet", journal_snippet),
+    ])
+}
 
-    claude_api::call_api_stream(&app, &cfg.api_key, &cfg.model, &system_prompt, &user_msg).await
+#[tauri::command(rename_all = "snake_case")]
+pub async fn generate_theater(date: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
+    let cfg = config::load_config();
+    let data_dir = PathBuf::from(&cfg.data_dir);
+    let prompt = prompts::render(&data_dir, "theater", &theater_vars(&cfg, &data_dir, &date))?;
+    ai_client::chat_prompt(&app, Feature::Theater, request_id, &prompt).await
 }
 
 #[tauri::command]
@@ -324,23 +573,14 @@ pub fn extract_today_words() -> Result<Vec<(String, String)>, String> {
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn generate_vocab_examples(date: String, words: Vec<(String, String)>, app: AppHandle) -> Result<String, String> {
-    let cfg = config::load_config();
-    if cfg.api_key.is_empty() {
-        return Err("请先在设置中填写 API Key".to_string());
-    }
-
+pub async fn generate_vocab_examples(date: String, words: Vec<(String, String)>, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
     let mut word_list = String::new();
     for (word, source) in &words {
         word_list.push_str(&format!("- {} (来源: {})\n", word, source));
     }
 
-    let system_prompt = "你是一个英语学习助手。为每个英文单词生成3种风格的例句。\n\
-        输出 JSON 数组格式，每个元素：{\"word\":\"xxx\",\"examples\":[{\"style\":\"日常\",\"text\":\"...\"},{\"style\":\"网文\",\"text\":\"...\"},{\"style\":\"程序员\",\"text\":\"...\"}]}\n\
-        网文风格要有中二感，程序员风格要有吐槽感。只输出 JSON，不要其他内容。";
-
-    let user_msg = format!("请为以下单词生成例句：\n{}", word_list);
-    claude_api::call_api_stream(&app, &cfg.api_key, &cfg.model, system_prompt, &user_msg).await
+    let prompt = prompts::render(&get_data_dir(), "vocab", &prompts::vars([("word_list", word_list)]))?;
+    ai_client::chat_prompt(&app, Feature::Vocab, request_id, &prompt).await
 }
 
 #[tauri::command]
@@ -365,11 +605,8 @@ pub fn get_alchemy_recipes() -> Result<Vec<inspiration::Recipe>, String> {
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn alchemy_synthesize(note_ids: Vec<u32>, app: AppHandle) -> Result<String, String> {
+pub async fn alchemy_synthesize(note_ids: Vec<u32>, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
     let cfg = config::load_config();
-    if cfg.api_key.is_empty() {
-        return Err("请先在设置中填写 API Key".to_string());
-    }
     let data_dir = get_data_dir();
     let store = inspiration::load_notes(&data_dir);
 
@@ -388,24 +625,14 @@ pub async fn alchemy_synthesize(note_ids: Vec<u32>, app: AppHandle) -> Result<St
             n.id, n.text, n.mood, n.tags.join("/")));
     }
 
-    let persona = crate::personality::get(&cfg.personality);
-    let system_prompt = format!(
-        "{}\n你是一个灵感炼金师。将用户提供的零散灵感碎片合成为一段完整的、可用的创作素材。\n\
-         要求：\n\
-         - 找出灵感之间的隐藏关联\n\
-         - 合成一个有趣的设定/场景/段落（200-400字）\n\
-         - 给合成结果起一个标题\n\
-         - 输出格式：第一行是标题，空一行后是正文\n\
-         - 用中文",
-        persona.ai_persona
-    );
-
-    let user_msg = format!(
-        "请将以下灵感碎片炼金合成（请给出全新的、不同于之前的创意角度，seed={}）：\n{}",
-        chrono::Local::now().timestamp_millis(),
-        material
-    );
-    claude_api::call_api_stream(&app, &cfg.api_key, &cfg.model, &system_prompt, &user_msg).await
+    let vars = prompts::vars([
+        ("persona", crate::personality::get(&cfg.personality).ai_persona.to_string()),
+        // 每次不同的种子，避免同样的灵感总合成出相似的结果
+        ("seed", chrono::Local::now().timestamp_millis().to_string()),
+        ("material", material),
+    ]);
+    let prompt = prompts::render(&data_dir, "alchemy", &vars)?;
+    ai_client::chat_prompt(&app, Feature::Alchemy, request_id, &prompt).await
 }
 
 #[tauri::command]
@@ -634,16 +861,133 @@ pub fn search_worldbuilding(book_id: String, keyword: String) -> Result<Vec<writ
 
 // === AI Provider 命令 ===
 
+/// AI 用量明细和汇总，range 格式同报告：2026-03、2026-W10、2026-03-01..2026-03-15
+#[tauri::command]
+pub fn get_ai_usage(range: String) -> Result<ai_usage::UsageReport, String> {
+    let period = activity::ReportPeriod::parse(&range)?;
+    Ok(ai_usage::report(&get_data_dir(), period))
+}
+
+/// 最近的 AI 生成记录（新的在前），feature 为空时列出所有功能，limit 默认 50
+#[tauri::command]
+pub fn list_ai_history(feature: Option<String>, limit: Option<usize>) -> Result<Vec<ai_history::HistoryEntry>, String> {
+    let feature = match feature.filter(|f| !f.is_empty()) {
+        Some(key) => Some(parse_feature(&key)?),
+        None => None,
+    };
+    Ok(ai_history::list(&get_data_dir(), feature, limit.unwrap_or(50)))
+}
+
+/// 取回一次过去的生成，id 为当时的请求 id
+#[tauri::command]
+pub fn restore_ai_history(feature: String, id: String) -> Result<ai_history::HistoryEntry, String> {
+    ai_history::get(&get_data_dir(), parse_feature(&feature)?, &id)
+}
+
+fn parse_feature(key: &str) -> Result<Feature, String> {
+    Feature::from_key(key).ok_or_else(|| format!("未知功能 {}", key))
+}
+
+/// 取消进行中的 AI 请求，返回是否找到该请求
+#[tauri::command]
+pub fn cancel_ai_request(id: String) -> bool {
+    ai_client::cancel(&id)
+}
+
 #[tauri::command]
 pub async fn test_ai_provider(provider: ai_provider::AIProvider) -> Result<bool, String> {
     provider.test_connection().await
 }
 
+// === 提示词模板 ===
+
+#[tauri::command]
+pub fn list_prompts() -> Result<prompts::PromptList, String> {
+    Ok(prompts::list(&get_data_dir()))
+}
+
+#[tauri::command]
+pub fn save_prompt(name: String, system: String, user: String) -> Result<prompts::PromptInfo, String> {
+    prompts::save(&get_data_dir(), &name, &system, &user)
+}
+
+#[tauri::command]
+pub fn reset_prompt(name: String) -> Result<prompts::PromptInfo, String> {
+    prompts::reset(&get_data_dir(), &name)
+}
+
+/// 预览渲染后的提示词：日记、周报和小剧场用今天的真实数据，其余变量可由 vars 指定，没给的用示例值
+#[tauri::command]
+pub fn preview_prompt(name: String, vars: Option<prompts::Vars>) -> Result<prompts::RenderedPrompt, String> {
+    let cfg = config::load_config();
+    let data_dir = PathBuf::from(&cfg.data_dir);
+    let vars = vars.unwrap_or_default();
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let date = vars.get("date").cloned().unwrap_or(today);
+    let mut live = match name.as_str() {
+        "diary_generate" => claude_api::generate_vars(&cfg.language, &cfg.personality, &date, "", &data_dir),
+        "report" => {
+            let period = activity::ReportPeriod::last_days(7);
+            claude_api::report_vars(&cfg.language, &cfg.personality, &period, &data_dir)
+        }
+        "theater" => theater_vars(&cfg, &data_dir, &date),
+        _ => prompts::vars([("persona", crate::personality::get(&cfg.personality).ai_persona.to_string())]),
+    };
+    live.extend(vars);
+    prompts::preview(&data_dir, &name, live)
+}
+
+/// 命令行 --ai 使用：按预览的方式渲染模板，用模板所属功能的引擎（或模拟引擎）流式输出，不依赖界面；真实引擎照常检查预算并记录用量
+pub async fn run_prompt<F>(name: &str, vars: prompts::Vars, mock: bool, on_chunk: F) -> Result<ai_client::Completion, String>
+where
+    F: FnMut(&str),
+{
+    let cfg = config::load_config();
+    let data_dir = PathBuf::from(&cfg.data_dir);
+    let feature_key = prompts::get(&data_dir, name)?.feature;
+    let feature = parse_feature(&feature_key)?;
+    let prompt = preview_prompt(name.to_string(), Some(vars))?;
+    if let Some(w) = &prompt.warning {
+        eprintln!("{}，本次使用内置模板", w);
+    }
+    if mock {
+        // 临时的模拟引擎不计费，也不往真实数据目录写用量
+        return ai_client::stream_chat(&crate::ai_mock::provider(), feature, &prompt.system, &prompt.user, on_chunk, |_, _, _| {})
+            .await
+            .map_err(|e| e.to_string());
+    }
+    // 和界面里的调用一样受月度预算限制，用量写入 ai_usage
+    let provider = ai_client::provider_for(&cfg, feature)?;
+    let meter = ai_client::Meter::start(&data_dir, &provider, feature, &prompt.system, &prompt.user).map_err(|e| e.to_string())?;
+    let mut on_chunk = on_chunk;
+    let result = ai_client::stream_chat(
+        &provider,
+        feature,
+        &prompt.system,
+        &prompt.user,
+        |chunk| {
+            meter.received(chunk);
+            on_chunk(chunk);
+        },
+        |_, _, _| {},
+    )
+    .await;
+    match result {
+        Ok(done) => {
+            meter.finish(&done).1?;
+            Ok(done)
+        }
+        Err(e) => Err(match meter.fail() {
+            Ok(()) => e.to_string(),
+            Err(w) => format!("{}；{}", e, w),
+        }),
+    }
+}
+
 // === AI 写作辅助命令 ===
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn ai_writing_suggest(_book_id: String, _chapter_id: String, context: String, app: AppHandle) -> Result<String, String> {
-    let provider = ai_provider::get_writing_provider()?;
+pub async fn ai_writing_suggest(_book_id: String, _chapter_id: String, context: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
 
     // 收集人物和设定信息作为上下文
     let data_dir = get_data_dir();
@@ -653,21 +997,14 @@ pub async fn ai_writing_suggest(_book_id: String, _chapter_id: String, context:
         char_info.push_str(&format!("- {}（{}）\n", c.name, c.role));
     }
 
-    let system = format!(
-        "你是一个小说写作助手。根据以下上下文，给出3个不同的后续发展方向建议。\n\
-         每个建议用一句话概括方向，再用2-3句话描述具体可以怎么写。\n\
-         不要直接写正文，只给思路。\n\n\
-         {}",
-        if char_info.is_empty() { String::new() } else { format!("已知人物：\n{}\n", char_info) }
-    );
-
-    let user_msg = format!("当前内容：\n{}", context);
-    provider.chat_stream(&app, &system, &user_msg).await
+    let characters = if char_info.is_empty() { String::new() } else { format!("已知人物：\n{}\n", char_info) };
+    let vars = prompts::vars([("characters", characters), ("context", context)]);
+    let prompt = prompts::render(&data_dir, "writing_suggest", &vars)?;
+    ai_client::chat_prompt(&app, Feature::Writing, request_id, &prompt).await
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn ai_consistency_check(book_id: String, chapter_id: String, app: AppHandle) -> Result<String, String> {
-    let provider = ai_provider::get_writing_provider()?;
+pub async fn ai_consistency_check(book_id: String, chapter_id: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
     let data_dir = get_data_dir();
 
     let chapter_content = writing::load_chapter_content(&data_dir, chapter_id);
@@ -686,20 +1023,13 @@ pub async fn ai_consistency_check(book_id: String, chapter_id: String, app: AppH
         .unwrap_or_default()
         .chars().take(2000).collect();
 
-    let system = "你是一个小说校对助手。对照以下设定资料，检查这章内容有没有矛盾或不一致的地方。\n\
-                  如果发现矛盾，指出具体位置和原因。如果没有问题，说\"未发现矛盾\"。";
-
-    let user_msg = format!(
-        "设定资料：\n{}\n\n人物档案：\n{}\n\n本章内容：\n{}",
-        wb_str, char_info, chapter_content
-    );
-
-    provider.chat_stream(&app, system, &user_msg).await
+    let vars = prompts::vars([("worldbuilding", wb_str), ("characters", char_info), ("chapter", chapter_content)]);
+    let prompt = prompts::render(&data_dir, "consistency_check", &vars)?;
+    ai_client::chat_prompt(&app, Feature::Writing, request_id, &prompt).await
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn ai_foreshadow_detect(_book_id: String, chapter_id: String, app: AppHandle) -> Result<String, String> {
-    let provider = ai_provider::get_writing_provider()?;
+pub async fn ai_foreshadow_detect(_book_id: String, chapter_id: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
     let data_dir = get_data_dir();
 
     let chapter_content = writing::load_chapter_content(&data_dir, chapter_id);
@@ -714,21 +1044,14 @@ pub async fn ai_foreshadow_detect(_book_id: String, chapter_id: String, app: App
         fs_info.push_str(&format!("- [{}] {}：{}\n", status_str, f.id, f.description));
     }
 
-    let system = "你是一个小说分析助手。阅读以下章节内容，找出可能是伏笔的地方（暗示、悬念、未解释的细节）。\n\
-                  列出你发现的潜在伏笔，以及是否可能回收了已有伏笔。";
-
-    let user_msg = format!(
-        "已知伏笔列表：\n{}\n\n本章内容：\n{}",
-        if fs_info.is_empty() { "（暂无）".to_string() } else { fs_info },
-        chapter_content
-    );
-
-    provider.chat_stream(&app, system, &user_msg).await
+    let foreshadows = if fs_info.is_empty() { "（暂无）".to_string() } else { fs_info };
+    let vars = prompts::vars([("foreshadows", foreshadows), ("chapter", chapter_content)]);
+    let prompt = prompts::render(&data_dir, "foreshadow_detect", &vars)?;
+    ai_client::chat_prompt(&app, Feature::Writing, request_id, &prompt).await
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn ai_chapter_summary(_book_id: String, chapter_id: String, app: AppHandle) -> Result<String, String> {
-    let provider = ai_provider::get_writing_provider()?;
+pub async fn ai_chapter_summary(_book_id: String, chapter_id: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
     let data_dir = get_data_dir();
 
     let chapter_content = writing::load_chapter_content(&data_dir, chapter_id);
@@ -736,14 +1059,13 @@ pub async fn ai_chapter_summary(_book_id: String, chapter_id: String, app: AppHa
         return Err("章节内容为空".to_string());
     }
 
-    let system = "你是一个小说摘要助手。用一句话概括这章的核心内容（不超过50字）。只输出摘要，不要其他内容。";
-    provider.chat_stream(&app, system, &chapter_content).await
+    let prompt = prompts::render(&data_dir, "chapter_summary", &prompts::vars([("chapter", chapter_content)]))?;
+    ai_client::chat_prompt(&app, Feature::Writing, request_id, &prompt).await
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn ai_bird_comment(text: String, prompt: String, app: AppHandle) -> Result<String, String> {
-    let provider = ai_provider::get_writing_provider()?;
-    provider.chat_stream(&app, &prompt, &text).await
+pub async fn ai_bird_comment(text: String, prompt: String, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
+    ai_client::chat(&app, Feature::Writing, request_id, &prompt, &text).await
 }
 
 // === 梦境日志命令 ===
@@ -769,15 +1091,15 @@ pub fn update_dream_analysis(id: String, analysis: String) -> Result<(), String>
 }
 
 #[tauri::command(rename_all = "snake_case")]
-pub async fn ai_dream_analysis(title: String, content: String, mood: String, lucid: bool, app: AppHandle) -> Result<String, String> {
-    let provider = ai_provider::get_writing_provider()?;
+pub async fn ai_dream_analysis(title: String, content: String, mood: String, lucid: bool, request_id: Option<String>, app: AppHandle) -> Result<String, String> {
 
     let lucid_str = if lucid { "是" } else { "否" };
-    let system = "你是一位温柔的梦境分析师。请分析以下梦境，从心理学角度给出简短解读（3-5句话），语气温暖有趣，不要太严肃。";
-    let user_msg = format!(
-        "梦境标题：{}\n梦境内容：{}\n情绪：{}\n是否清醒梦：{}",
-        title, content, mood, lucid_str
-    );
-
-    provider.chat_stream(&app, system, &user_msg).await
+    let vars = prompts::vars([
+        ("title", title),
+        ("content", content),
+        ("mood", mood),
+        ("lucid", lucid_str.to_string()),
+    ]);
+    let prompt = prompts::render(&get_data_dir(), "dream_analysis", &vars)?;
+    ai_client::chat_prompt(&app, Feature::Dream, request_id, &prompt).await
 }
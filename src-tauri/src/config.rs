@@ -1,27 +1,68 @@
+use crate::ai_provider::AIProvider;
+use crate::personality::CustomPersonality;
+use crate::privacy::PrivacyConfig;
+use crate::secrets::SecretStore;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
+use std::io;
 use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+/// 当前配置格式版本，load_config 会把旧版本逐级迁移上来
+pub const CONFIG_VERSION: u32 = 2;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
+    /// 配置格式版本，旧配置没有该字段视为 0
+    #[serde(default)]
+    pub version: u32,
+    /// API Key 存放位置
     #[serde(default)]
-    pub api_key: String,
-    #[serde(default = "default_model")]
-    pub model: String,
+    pub secret_store: SecretStore,
     #[serde(default = "default_language")]
     pub language: String,
     #[serde(default = "default_interval")]
     pub interval: u64,
+    /// 超过该秒数无键盘/鼠标输入视为离开，0 表示关闭
+    #[serde(default = "default_idle_threshold")]
+    pub idle_threshold_secs: u64,
     #[serde(default)]
     pub data_dir: String,
     #[serde(default)]
     pub daily_goal_minutes: u64,
     #[serde(default = "default_personality")]
     pub personality: String,
-}
-
-fn default_model() -> String {
-    "deepseek-chat".to_string()
+    /// 隐私排除规则
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    /// 浏览器扩展上报标签页的本地端口，0 表示关闭（只从标题推断域名）
+    #[serde(default)]
+    pub browser_bridge_port: u16,
+    /// 启动时自动开始监测，不用等前端点击
+    #[serde(default)]
+    pub auto_start_monitor: bool,
+    /// 开机登录后自动运行
+    #[serde(default)]
+    pub launch_on_login: bool,
+    /// 多 AI 引擎，api_key 保存后为密钥引用（keyring:… / secret-file:…）
+    #[serde(default)]
+    pub ai_providers: Vec<AIProvider>,
+    /// 默认使用的引擎 id
+    #[serde(default)]
+    pub active_provider: String,
+    /// 按功能指定引擎：diary / theater / vocab / alchemy / writing / dream → 引擎 id，未指定时用 active_provider
+    #[serde(default)]
+    pub feature_providers: BTreeMap<String, String>,
+    /// 相同提示词在该小时数内直接返回生成历史中的结果，0 表示关闭缓存
+    #[serde(default)]
+    pub ai_cache_hours: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_personality: Option<CustomPersonality>,
+    /// 未声明的字段（如前端的 bird_size）原样保留，保存时写回
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 fn default_language() -> String {
@@ -32,6 +73,10 @@ fn default_interval() -> u64 {
     30
 }
 
+fn default_idle_threshold() -> u64 {
+    300
+}
+
 fn default_personality() -> String {
     "gentle".to_string()
 }
@@ -39,13 +84,24 @@ fn default_personality() -> String {
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
-            api_key: String::new(),
-            model: default_model(),
+            version: CONFIG_VERSION,
+            secret_store: SecretStore::default(),
             language: default_language(),
             interval: default_interval(),
+            idle_threshold_secs: default_idle_threshold(),
             data_dir: String::new(),
             daily_goal_minutes: 0,
             personality: default_personality(),
+            privacy: PrivacyConfig::default(),
+            browser_bridge_port: 0,
+            auto_start_monitor: false,
+            launch_on_login: false,
+            ai_providers: vec![],
+            active_provider: String::new(),
+            feature_providers: BTreeMap::new(),
+            ai_cache_hours: 0,
+            custom_personality: None,
+            extra: serde_json::Map::new(),
         }
     }
 }
@@ -90,26 +146,232 @@ pub fn app_dir() -> PathBuf {
         .unwrap_or_else(|| PathBuf::from("."))
 }
 
+/// 内存中的配置：首次读取时从磁盘加载，保存或文件被改动时更新
+static CURRENT: RwLock<Option<AppConfig>> = RwLock::new(None);
+/// 启动时 config.json 读取或解析失败的原因；此时内存中是默认配置，文件保持原样
+static LOAD_ERROR: RwLock<Option<String>> = RwLock::new(None);
+
+/// 当前配置（内存缓存，不重复读盘）
 pub fn load_config() -> AppConfig {
-    let path = config_path();
-    if path.exists() {
-        if let Ok(content) = fs::read_to_string(&path) {
-            if let Ok(mut cfg) = serde_json::from_str::<AppConfig>(&content) {
-                if cfg.data_dir.is_empty() {
-                    cfg.data_dir = app_dir().join("data").to_string_lossy().to_string();
+    if let Some(cfg) = CURRENT.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return cfg.clone();
+    }
+    let cfg = read_config_file().unwrap_or_else(|e| {
+        *LOAD_ERROR.write().unwrap_or_else(|e| e.into_inner()) = Some(e);
+        with_data_dir(AppConfig::default())
+    });
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Some(cfg.clone());
+    cfg
+}
+
+/// config.json 当前无法使用的原因，没有问题时为 None
+pub fn load_error() -> Option<String> {
+    LOAD_ERROR.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// 从 config.json 读取并迁移到当前版本；文件不存在时为默认配置，读取或解析失败时返回错误
+fn read_config_file() -> Result<AppConfig, String> {
+    let content = match fs::read_to_string(config_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(with_data_dir(AppConfig::default())),
+        Err(e) => return Err(format!("读取 config.json 失败: {}", e)),
+    };
+    let mut raw: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| format!("config.json 格式有误: {}", e))?;
+    let migrated = migrate(&mut raw);
+    let cfg: AppConfig = serde_json::from_value(raw).map_err(|e| format!("config.json 格式有误: {}", e))?;
+    if migrated {
+        let _ = write_file(&cfg);
+    }
+    Ok(with_data_dir(cfg))
+}
+
+fn with_data_dir(mut cfg: AppConfig) -> AppConfig {
+    if cfg.data_dir.is_empty() {
+        cfg.data_dir = app_dir().join("data").to_string_lossy().to_string();
+    }
+    cfg
+}
+
+/// 替换内存中的配置，内容没变时返回 false
+fn replace_current(cfg: AppConfig) -> bool {
+    let mut current = CURRENT.write().unwrap_or_else(|e| e.into_inner());
+    let changed = match current.as_ref() {
+        Some(old) => serde_json::to_value(old).ok() != serde_json::to_value(&cfg).ok(),
+        None => true,
+    };
+    *current = Some(cfg);
+    changed
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// 监视 config.json，被手动修改后重新加载并回调（内容与内存中相同时不回调）
+///
+/// 改到一半或类型不对的文件无法解析时保留当前配置，把原因交给 on_error，不会用默认值覆盖
+pub fn watch<F, E>(on_change: F, on_error: E)
+where
+    F: Fn(AppConfig) + Send + 'static,
+    E: Fn(String) + Send + 'static,
+{
+    std::thread::spawn(move || {
+        let path = config_path();
+        let mut last = modified(&path);
+        if let Some(e) = load_error() {
+            on_error(e);
+        }
+        loop {
+            std::thread::sleep(Duration::from_secs(2));
+            let now = modified(&path);
+            if now == last {
+                continue;
+            }
+            last = now;
+            match read_config_file() {
+                Ok(cfg) => {
+                    *LOAD_ERROR.write().unwrap_or_else(|e| e.into_inner()) = None;
+                    if replace_current(cfg.clone()) {
+                        on_change(cfg);
+                    }
+                }
+                Err(e) => {
+                    *LOAD_ERROR.write().unwrap_or_else(|e| e.into_inner()) = Some(e.clone());
+                    on_error(e);
                 }
-                return cfg;
             }
         }
+    });
+}
+
+/// 把原始配置逐级迁移到 CONFIG_VERSION，返回是否有改动
+fn migrate(raw: &mut serde_json::Value) -> bool {
+    let Some(obj) = raw.as_object_mut() else {
+        return false;
+    };
+    let version = obj.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version >= CONFIG_VERSION {
+        return false;
     }
-    let mut cfg = AppConfig::default();
-    cfg.data_dir = app_dir().join("data").to_string_lossy().to_string();
-    cfg
+
+    if version < 1 {
+        // v0 → v1：前端直接写入的字段可能为 null 或缺 id/name，整理成类型化配置能解析的形状
+        for key in ["ai_providers", "active_provider", "writing_provider", "custom_personality"] {
+            if obj.get(key).is_some_and(|v| v.is_null()) {
+                obj.remove(key);
+            }
+        }
+        if let Some(serde_json::Value::Array(providers)) = obj.get_mut("ai_providers") {
+            providers.retain(|p| p.is_object());
+            for (i, p) in providers.iter_mut().filter_map(|p| p.as_object_mut()).enumerate() {
+                let id = match p.get("id").and_then(|v| v.as_str()) {
+                    Some(id) if !id.is_empty() => id.to_string(),
+                    _ => format!("provider-{}", i + 1),
+                };
+                if !p.get("name").is_some_and(|v| v.is_string()) {
+                    p.insert("name".into(), id.clone().into());
+                }
+                p.insert("id".into(), id.into());
+            }
+        }
+    }
+
+    if version < 2 {
+        // v1 → v2：旧版单一的 api_key/model 变成一个 DeepSeek 引擎，writing_provider 并入按功能指定
+        let api_key = obj.remove("api_key").and_then(|v| v.as_str().map(String::from)).unwrap_or_default();
+        let model = obj.remove("model").and_then(|v| v.as_str().map(String::from));
+        if !api_key.is_empty() {
+            let providers = obj.entry("ai_providers").or_insert_with(|| serde_json::json!([]));
+            if let serde_json::Value::Array(list) = providers {
+                let taken = |id: &str| list.iter().any(|p| p.get("id").and_then(|v| v.as_str()) == Some(id));
+                let id = if taken("deepseek") { "deepseek-legacy" } else { "deepseek" };
+                list.insert(0, serde_json::json!({
+                    "id": id,
+                    "name": "DeepSeek",
+                    "api_key": api_key,
+                    "base_url": "https://api.deepseek.com/v1",
+                    "model": model.unwrap_or_else(|| "deepseek-chat".to_string()),
+                }));
+                // 旧版日记（含周报月报）、小剧场、单词和炼金一直用的是这个密钥，逐个指定，不随 active_provider 换引擎
+                let active_empty = obj.get("active_provider").and_then(|v| v.as_str()).unwrap_or("").is_empty();
+                if active_empty {
+                    obj.insert("active_provider".into(), id.into());
+                }
+                let features = obj.entry("feature_providers").or_insert_with(|| serde_json::json!({}));
+                if let Some(map) = features.as_object_mut() {
+                    for feature in ["diary", "theater", "vocab", "alchemy"] {
+                        map.entry(feature).or_insert(id.into());
+                    }
+                }
+            }
+        }
+        if let Some(writing) = obj.remove("writing_provider").and_then(|v| v.as_str().map(String::from)) {
+            if !writing.is_empty() {
+                let features = obj.entry("feature_providers").or_insert_with(|| serde_json::json!({}));
+                if let Some(map) = features.as_object_mut() {
+                    map.entry("writing").or_insert(writing.into());
+                }
+            }
+        }
+    }
+
+    obj.insert("version".into(), CONFIG_VERSION.into());
+    true
 }
 
+/// 写入 config.json 并更新内存中的配置
+///
+/// 磁盘上的文件无法解析时拒绝写入，以免界面上的默认值覆盖用户手写的内容
 pub fn save_config_to_file(cfg: &AppConfig) -> Result<(), String> {
-    let path = config_path();
-    let json = serde_json::to_string_pretty(cfg).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| e.to_string())?;
+    if let Err(e) = read_config_file() {
+        return Err(format!("{}，请先手动修正或删除 config.json 再保存", e));
+    }
+    *LOAD_ERROR.write().unwrap_or_else(|e| e.into_inner()) = None;
+    let mut cfg = cfg.clone();
+    cfg.version = CONFIG_VERSION;
+    write_file(&cfg)?;
+    replace_current(cfg);
     Ok(())
 }
+
+fn write_file(cfg: &AppConfig) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(cfg).map_err(|e| e.to_string())?;
+    fs::write(config_path(), json).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_key_keeps_its_features_when_another_provider_is_active() {
+        let mut raw = serde_json::json!({
+            "version": 1,
+            "api_key": "sk-legacy",
+            "model": "deepseek-chat",
+            "ai_providers": [{ "id": "claude", "name": "Claude", "protocol": "anthropic", "api_key": "sk-ant" }],
+            "active_provider": "claude",
+            "writing_provider": "claude",
+        });
+        assert!(migrate(&mut raw));
+        let cfg: AppConfig = serde_json::from_value(raw).unwrap();
+        assert_eq!(cfg.active_provider, "claude");
+        assert_eq!(cfg.ai_providers[0].id, "deepseek");
+        assert_eq!(cfg.ai_providers[0].api_key, "sk-legacy");
+        for feature in ["diary", "theater", "vocab", "alchemy"] {
+            assert_eq!(cfg.feature_providers[feature], "deepseek", "{}", feature);
+        }
+        assert_eq!(cfg.feature_providers["writing"], "claude");
+        assert!(!cfg.feature_providers.contains_key("dream"));
+    }
+
+    #[test]
+    fn legacy_key_becomes_active_when_none_is_set() {
+        let mut raw = serde_json::json!({ "version": 1, "api_key": "sk-legacy" });
+        assert!(migrate(&mut raw));
+        let cfg: AppConfig = serde_json::from_value(raw).unwrap();
+        assert_eq!(cfg.active_provider, "deepseek");
+        assert_eq!(cfg.feature_providers["diary"], "deepseek");
+    }
+}
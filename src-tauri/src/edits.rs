@@ -0,0 +1,167 @@
+use crate::tracker::ActivityEntry;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const TS_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// 手动修改操作，按 (开始时间, 进程名) 定位记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum EditOp {
+    /// 补录一条离线活动（如看纸质书）
+    Insert { entry: ActivityEntry },
+    /// 在 at 时刻把记录切成两段
+    Split { ts: String, exe: String, at: String },
+    /// 覆盖记录的分类，category 为空表示恢复按规则分类
+    SetCategory { ts: String, exe: String, category: Option<String> },
+    Delete { ts: String, exe: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEdit {
+    pub id: u64,
+    /// 修改时间
+    pub created_at: String,
+    #[serde(flatten)]
+    pub op: EditOp,
+}
+
+/// 修改记录与原始 JSONL 分开存放：activity_edits/YYYY-MM-DD.jsonl，只追加，撤销时才重写
+pub fn edits_path(data_dir: &PathBuf, date: &str) -> PathBuf {
+    data_dir.join("activity_edits").join(format!("{}.jsonl", date))
+}
+
+static EDITS_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn load(data_dir: &PathBuf, date: &str) -> Vec<ActivityEdit> {
+    let content = match fs::read_to_string(edits_path(data_dir, date)) {
+        Ok(c) => c,
+        Err(_) => return vec![],
+    };
+    content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l.trim()).ok())
+        .collect()
+}
+
+fn parse_ts(ts: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(ts, TS_FORMAT).map_err(|_| format!("时间格式应为 YYYY-MM-DDTHH:MM:SS: {}", ts))
+}
+
+/// 在原始记录上依次应用修改；定位不到记录的修改跳过
+pub fn apply(entries: &mut Vec<ActivityEntry>, edits: &[ActivityEdit]) {
+    if edits.is_empty() {
+        return;
+    }
+    for edit in edits {
+        match &edit.op {
+            EditOp::Insert { entry } => entries.push(entry.clone()),
+            EditOp::Split { ts, exe, at } => {
+                let Some(i) = find(entries, ts, exe) else { continue };
+                let (Ok(start), Ok(cut)) = (parse_ts(ts), parse_ts(at)) else { continue };
+                let first = (cut - start).num_seconds();
+                if first <= 0 || first as u64 >= entries[i].duration {
+                    continue;
+                }
+                let mut second = entries[i].clone();
+                second.ts = at.clone();
+                second.duration = entries[i].duration - first as u64;
+                entries[i].duration = first as u64;
+                entries.insert(i + 1, second);
+            }
+            EditOp::SetCategory { ts, exe, category } => {
+                if let Some(i) = find(entries, ts, exe) {
+                    entries[i].category = category.clone();
+                }
+            }
+            EditOp::Delete { ts, exe } => {
+                if let Some(i) = find(entries, ts, exe) {
+                    entries.remove(i);
+                }
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.ts.cmp(&b.ts));
+}
+
+fn find(entries: &[ActivityEntry], ts: &str, exe: &str) -> Option<usize> {
+    entries.iter().position(|e| e.ts == ts && e.exe == exe)
+}
+
+/// 校验并追加一条修改，返回修改后的当天记录
+pub fn add(data_dir: &PathBuf, op: EditOp) -> Result<Vec<ActivityEntry>, String> {
+    let ts = match &op {
+        EditOp::Insert { entry } => &entry.ts,
+        EditOp::Split { ts, .. } | EditOp::SetCategory { ts, .. } | EditOp::Delete { ts, .. } => ts,
+    };
+    parse_ts(ts)?;
+    let date = ts[..10].to_string();
+
+    let current = crate::activity::load_entries(data_dir, &date);
+    match &op {
+        EditOp::Insert { entry } => {
+            if entry.duration == 0 {
+                return Err("时长不能为 0".into());
+            }
+            if find(&current, &entry.ts, &entry.exe).is_some() {
+                return Err("该时间已有同名记录".into());
+            }
+        }
+        EditOp::Split { ts, exe, at } => {
+            let i = find(&current, ts, exe).ok_or("找不到要切分的记录")?;
+            let first = (parse_ts(at)? - parse_ts(ts)?).num_seconds();
+            if first <= 0 || first as u64 >= current[i].duration {
+                return Err("切分时间必须落在记录时段内".into());
+            }
+        }
+        EditOp::SetCategory { ts, exe, .. } | EditOp::Delete { ts, exe } => {
+            find(&current, ts, exe).ok_or("找不到该记录")?;
+        }
+    }
+
+    let guard = EDITS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let id = load(data_dir, &date).iter().map(|e| e.id).max().unwrap_or(0) + 1;
+    let edit = ActivityEdit {
+        id,
+        created_at: chrono::Local::now().format(TS_FORMAT).to_string(),
+        op,
+    };
+    let path = edits_path(data_dir, &date);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    let line = serde_json::to_string(&edit).map_err(|e| e.to_string())?;
+    writeln!(f, "{}", line).map_err(|e| e.to_string())?;
+    drop(f);
+    drop(guard);
+
+    Ok(crate::activity::load_entries(data_dir, &date))
+}
+
+/// 撤销一条修改，返回撤销后的当天记录
+pub fn undo(data_dir: &PathBuf, date: &str, id: u64) -> Result<Vec<ActivityEntry>, String> {
+    let guard = EDITS_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut edits = load(data_dir, date);
+    let before = edits.len();
+    edits.retain(|e| e.id != id);
+    if edits.len() == before {
+        return Err("找不到该修改".into());
+    }
+    let path = edits_path(data_dir, date);
+    let lines: Vec<String> = edits.iter().filter_map(|e| serde_json::to_string(e).ok()).collect();
+    let tmp = path.with_extension("jsonl.tmp");
+    fs::write(&tmp, lines.join("\n") + "\n").map_err(|e| e.to_string())?;
+    fs::rename(&tmp, &path).map_err(|e| e.to_string())?;
+    drop(guard);
+
+    Ok(crate::activity::load_entries(data_dir, date))
+}
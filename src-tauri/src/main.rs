@@ -1,6 +1,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod active_window;
 mod activity;
+mod autostart;
+mod browser;
+mod categories;
 mod claude_api;
 mod commands;
 mod config;
@@ -9,8 +13,14 @@ mod journal;
 mod memes;
 mod mood;
 mod personality;
+mod privacy;
+mod prompts;
+mod relocate;
+mod rollup;
+mod secrets;
 mod social;
 mod tracker;
+mod transfer;
 mod tray_icon;
 mod villain;
 mod vocab;
@@ -18,7 +28,13 @@ mod world;
 mod theater;
 mod writing;
 mod ai_provider;
+mod ai_client;
+mod ai_error;
+mod ai_usage;
+mod ai_mock;
+mod ai_history;
 mod dream;
+mod edits;
 
 use commands::{TrayState, TrackerState};
 use std::sync::Mutex;
@@ -26,13 +42,94 @@ use tauri::{
     image::Image,
     menu::{MenuBuilder, MenuItemBuilder},
     tray::TrayIconBuilder,
-    Manager, WindowEvent,
+    Emitter, Manager, WindowEvent,
 };
 
 fn main() {
     let cfg = config::load_config();
     let data_dir = std::path::PathBuf::from(&cfg.data_dir);
-    let tracker = tracker::Tracker::new(cfg.interval, data_dir);
+
+    // 无界面回放：xiaolanniao --replay script.json [--out 目录]，按脚本样本写入活动记录后退出
+    // 不指定 --out 时写到新建的临时目录，不会碰真实数据目录和运行中的检查点
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() >= 3 && args[1] == "--replay" {
+        let script = std::path::PathBuf::from(&args[2]);
+        let out = match args.iter().position(|a| a == "--out").and_then(|i| args.get(i + 1)) {
+            Some(dir) => std::path::PathBuf::from(dir),
+            None => std::env::temp_dir().join(format!(
+                "xiaolanniao-replay-{}-{}",
+                std::process::id(),
+                chrono::Local::now().format("%Y%m%d%H%M%S")
+            )),
+        };
+        if out == data_dir {
+            eprintln!("回放失败: --out 不能是当前数据目录");
+            std::process::exit(1);
+        }
+        eprintln!("回放输出目录: {}", out.display());
+        match tracker::replay_file(&script, 1, out) {
+            Ok(entries) => {
+                for e in &entries {
+                    println!("{}", serde_json::to_string(e).unwrap_or_default());
+                }
+            }
+            Err(e) => {
+                eprintln!("回放失败: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // 无界面调用 AI：xiaolanniao --ai <模板名> [--mock] [变量=值 ...]，流式输出到终端后退出
+    if args.len() >= 3 && args[1] == "--ai" {
+        let mock = args[3..].iter().any(|a| a == "--mock");
+        let vars: prompts::Vars = args[3..]
+            .iter()
+            .filter_map(|a| a.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let run = commands::run_prompt(&args[2], vars, mock, |chunk| {
+            use std::io::Write;
+            print!("{}", chunk);
+            let _ = std::io::stdout().flush();
+        });
+        match tauri::async_runtime::block_on(run) {
+            Ok(done) => {
+                println!();
+                if let Some(failure) = done.incomplete {
+                    eprintln!("内容不完整: {}", failure);
+                }
+            }
+            Err(e) => {
+                eprintln!("AI 调用失败: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if cfg.browser_bridge_port > 0 {
+        if let Err(e) = browser::start_bridge(cfg.browser_bridge_port) {
+            commands::add_startup_warning(e);
+        }
+    }
+
+    // 把 config.json 里的明文 API Key 转存到系统钥匙串或加密文件
+    if let Err(e) = secrets::migrate_plaintext() {
+        commands::add_startup_warning(e);
+    }
+
+    // 每次启动刷新开机启动项，程序移动位置后路径仍然正确
+    if cfg.launch_on_login {
+        if let Err(e) = autostart::set_launch_on_login(true) {
+            commands::add_startup_warning(format!("刷新开机启动项失败: {}", e));
+        }
+    }
+    let auto_start_monitor = cfg.auto_start_monitor;
+
+    let tracker = tracker::Tracker::new(cfg.interval, cfg.idle_threshold_secs, data_dir)
+        .privacy(privacy::Privacy::new(&cfg.privacy));
 
     tauri::Builder::default()
         .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
@@ -46,7 +143,7 @@ fn main() {
         .plugin(tauri_plugin_dialog::init())
         .manage(TrackerState(Mutex::new(tracker)))
         .manage(TrayState(Mutex::new(None)))
-        .setup(|app| {
+        .setup(move |app| {
             let show = MenuItemBuilder::with_id("show", "显示窗口").build(app)?;
             let quit = MenuItemBuilder::with_id("quit", "退出").build(app)?;
             let menu = MenuBuilder::new(app).items(&[&show, &quit]).build()?;
@@ -86,6 +183,22 @@ fn main() {
             let tray_state: tauri::State<TrayState> = app.state();
             *tray_state.0.lock().unwrap() = Some(tray);
 
+            // config.json 被手动修改时重新加载
+            let handle = app.handle().clone();
+            let error_handle = app.handle().clone();
+            config::watch(
+                move |cfg| commands::apply_config(&handle, &cfg),
+                move |e| {
+                    let _ = error_handle.emit("config-error", e);
+                },
+            );
+
+            if auto_start_monitor {
+                if let Err(e) = commands::start_tracking(app.handle()) {
+                    commands::add_startup_warning(format!("自动开始监测失败: {}", e));
+                }
+            }
+
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -97,15 +210,37 @@ fn main() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::get_config,
+            commands::get_config_error,
+            commands::get_startup_warnings,
             commands::save_config,
+            commands::unlock_secrets,
             commands::start_monitor,
             commands::stop_monitor,
             commands::get_monitor_status,
             commands::get_today_activity,
             commands::load_activity,
             commands::get_activity_summary,
+            // 手动修改活动记录
+            commands::insert_activity,
+            commands::split_activity,
+            commands::set_activity_category,
+            commands::delete_activity,
+            commands::list_activity_edits,
+            commands::undo_activity_edit,
             commands::get_daily_totals,
             commands::get_range_summary,
+            commands::get_daily_totals_between,
+            commands::get_range_summary_between,
+            commands::resolve_period,
+            commands::get_activity_heatmap,
+            commands::export_activity,
+            commands::import_activity,
+            // 分类规则
+            commands::get_category_rules,
+            commands::save_category_rules,
+            commands::reset_category_rules,
+            commands::preview_category,
+            commands::reclassify_activity,
             commands::load_journal,
             commands::save_journal,
             commands::delete_journal,
@@ -114,7 +249,9 @@ fn main() {
             commands::ai_generate,
             commands::ai_organize,
             commands::ai_report,
+            commands::ai_report_period,
             commands::browse_directory,
+            commands::move_data_dir,
             commands::pick_and_read_file,
             commands::get_chick_mood,
             commands::get_social_status,
@@ -184,6 +321,15 @@ fn main() {
             commands::search_worldbuilding,
             // AI Provider
             commands::test_ai_provider,
+            commands::cancel_ai_request,
+            commands::get_ai_usage,
+            commands::list_ai_history,
+            commands::restore_ai_history,
+            // 提示词模板
+            commands::list_prompts,
+            commands::save_prompt,
+            commands::reset_prompt,
+            commands::preview_prompt,
             // AI 写作辅助
             commands::ai_writing_suggest,
             commands::ai_consistency_check,
@@ -1,4 +1,5 @@
-use crate::activity;
+use crate::rollup;
+use crate::categories;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -22,29 +23,15 @@ pub fn scan_patterns(data_dir: &PathBuf) -> Vec<MemeEntry> {
     for i in 0..7 {
         let date = today - chrono::Duration::days(i as i64);
         let ds = date.format("%Y-%m-%d").to_string();
-        let entries = activity::load_entries(data_dir, &ds);
-        if entries.is_empty() { continue; }
-        let summary = activity::summarize(&entries);
-        let total_sec: u64 = entries.iter().map(|e| e.duration).sum();
-        let has_late_night = entries.iter().any(|e| {
-            if let Some(h) = parse_hour(&e.ts) {
-                h >= 23 || h < 4
-            } else { false }
-        });
-        // 统计每个 exe 出现
-        let mut exe_set: HashMap<String, u64> = HashMap::new();
-        for e in &entries {
-            if e.duration < 30 { continue; }
-            let key = e.exe.to_lowercase();
-            *exe_set.entry(key).or_insert(0) += e.duration;
-        }
+        let day = rollup::day(data_dir, &ds);
+        if day.entries == 0 { continue; }
         let weekday = date.format("%u").to_string().parse::<u32>().unwrap_or(1);
         daily_data.push(DayInfo {
             date: ds,
-            summary,
-            total_sec,
-            has_late_night,
-            exe_map: exe_set,
+            summary: day.by_category,
+            total_sec: day.active_sec,
+            has_late_night: day.late_night,
+            exe_map: day.by_exe,
             is_weekend: weekday >= 6,
         });
     }
@@ -54,7 +41,8 @@ pub fn scan_patterns(data_dir: &PathBuf) -> Vec<MemeEntry> {
     // 模式1: 夜猫子 — 连续 3+ 天深夜有活动
     check_night_owl(&daily_data, &mut memes);
     // 模式2: XX 沉迷者 — 某分类连续 3+ 天占比 >40%
-    check_category_addict(&daily_data, &mut memes);
+    let cats = categories::load(data_dir).category_names();
+    check_category_addict(&daily_data, &cats, &mut memes);
     // 模式3: 老朋友 {exe} — 某 exe 连续 5+ 天出现
     check_old_friend(&daily_data, &mut memes);
     // 模式4: 周末战士 — 周末游戏时长 > 工作日 3 倍
@@ -126,17 +114,6 @@ struct DayInfo {
     is_weekend: bool,
 }
 
-fn parse_hour(ts: &str) -> Option<u32> {
-    // ts 格式: "2025-01-15T23:45:00" 或 "HH:MM:SS" 等
-    if ts.len() >= 13 && ts.contains('T') {
-        ts[11..13].parse().ok()
-    } else if ts.len() >= 2 {
-        ts[0..2].parse().ok()
-    } else {
-        None
-    }
-}
-
 fn simple_rand() -> usize {
     use std::time::SystemTime;
     let d = SystemTime::now()
@@ -162,9 +139,9 @@ fn check_night_owl(days: &[DayInfo], memes: &mut Vec<MemeEntry>) {
     }
 }
 
-fn check_category_addict(days: &[DayInfo], memes: &mut Vec<MemeEntry>) {
-    let cats = ["编程", "浏览器", "游戏", "视频", "社交", "文档", "音乐"];
-    for cat in &cats {
+fn check_category_addict(days: &[DayInfo], cats: &[String], memes: &mut Vec<MemeEntry>) {
+    for cat in cats {
+        if cat == categories::FALLBACK_CATEGORY { continue; }
         let consecutive = days.iter()
             .take_while(|d| {
                 if d.total_sec == 0 { return false; }
@@ -1,4 +1,4 @@
-use crate::activity;
+use crate::rollup;
 use crate::config;
 use crate::journal;
 use crate::memes;
@@ -15,8 +15,8 @@ pub struct ChickMood {
 
 /// 根据当前状态评估小鸡心情
 pub fn evaluate(data_dir: &PathBuf) -> ChickMood {
-    let mut result = evaluate_inner(data_dir);
     let cfg = config::load_config();
+    let mut result = evaluate_inner(data_dir, &cfg);
 
     // 生成 greeting（固定问候语 + 可能的梗）
     let base_greeting = crate::personality::get_greeting(&cfg.personality, &result.mood);
@@ -49,11 +49,10 @@ fn should_append_meme() -> bool {
     n % 100 < 80
 }
 
-fn evaluate_inner(data_dir: &PathBuf) -> ChickMood {
+fn evaluate_inner(data_dir: &PathBuf, cfg: &config::AppConfig) -> ChickMood {
     let now = chrono::Local::now();
     let hour = now.format("%H").to_string().parse::<u32>().unwrap_or(12);
     let today = now.format("%Y-%m-%d").to_string();
-    let cfg = config::load_config();
     let p = crate::personality::get(&cfg.personality);
     let goal_min = cfg.daily_goal_minutes;
 
@@ -68,9 +67,9 @@ fn evaluate_inner(data_dir: &PathBuf) -> ChickMood {
     }
 
     // 2. 加载今日活动
-    let entries = activity::load_entries(data_dir, &today);
-    let summary = activity::summarize(&entries);
-    let total_sec: u64 = entries.iter().map(|e| e.duration).sum();
+    let day = rollup::day(data_dir, &today);
+    let summary = day.by_category;
+    let total_sec = day.active_sec;
 
     // 计算目标进度
     let goal_pct = if goal_min > 0 {
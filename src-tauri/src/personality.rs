@@ -9,6 +9,27 @@ pub struct PersonalityGrowth {
     pub special_lines: Vec<String>,
 }
 
+/// 设置页「自定义」性格
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomPersonality {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub catchphrase: String,
+    /// 说话频率 1-5
+    #[serde(default = "default_level")]
+    pub speech_frequency: u8,
+    /// 语气 1-5
+    #[serde(default = "default_level")]
+    pub tone: u8,
+}
+
+fn default_level() -> u8 {
+    3
+}
+
 pub fn load_growth(data_dir: &PathBuf) -> PersonalityGrowth {
     let path = data_dir.join("personality_growth.json");
     if path.exists() {
@@ -0,0 +1,147 @@
+use crate::tracker::ActivityEntry;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// 隐私模式下记录的标题
+pub const PRIVATE_TITLE: &str = "[private]";
+
+/// 命中排除规则的窗口如何处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyMode {
+    /// 完全不记录
+    Drop,
+    /// 只记时长，标题记为 [private]，进程名清空
+    #[default]
+    Private,
+    /// 保留进程名，标题替换为哈希
+    Hash,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyConfig {
+    /// 排除的进程名（忽略大小写和 .exe 后缀）
+    #[serde(default)]
+    pub exclude_exes: Vec<String>,
+    /// 排除的标题正则
+    #[serde(default)]
+    pub exclude_title_patterns: Vec<String>,
+    /// 识别浏览器无痕/InPrivate 窗口
+    #[serde(default = "default_true")]
+    pub detect_incognito: bool,
+    #[serde(default)]
+    pub mode: PrivacyMode,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            exclude_exes: vec![],
+            exclude_title_patterns: vec![],
+            detect_incognito: true,
+            mode: PrivacyMode::default(),
+        }
+    }
+}
+
+/// 各浏览器无痕窗口标题中的标记
+const INCOGNITO_MARKERS: &[&str] = &[
+    "inprivate", "incognito", "private browsing", "私密浏览", "隐身", "无痕", "隐私浏览",
+];
+
+/// 编译后的隐私规则
+#[derive(Debug, Clone, Default)]
+pub struct Privacy {
+    mode: PrivacyMode,
+    exes: Vec<String>,
+    patterns: Vec<Regex>,
+    incognito: bool,
+}
+
+fn normalize_exe(exe: &str) -> String {
+    let lower = exe.trim().to_lowercase();
+    lower.strip_suffix(".exe").map(|s| s.to_string()).unwrap_or(lower)
+}
+
+impl Privacy {
+    /// 编译规则，无效的正则跳过（保存配置时已用 validate 校验过）
+    pub fn new(cfg: &PrivacyConfig) -> Self {
+        Self {
+            mode: cfg.mode,
+            exes: cfg.exclude_exes.iter().map(|e| normalize_exe(e)).filter(|e| !e.is_empty()).collect(),
+            patterns: cfg.exclude_title_patterns.iter().filter_map(|p| Regex::new(p).ok()).collect(),
+            incognito: cfg.detect_incognito,
+        }
+    }
+
+    /// 按当前配置文件加载
+    pub fn load() -> Self {
+        Self::new(&crate::config::load_config().privacy)
+    }
+
+    pub fn is_excluded(&self, title: &str, exe: &str) -> bool {
+        if !self.exes.is_empty() && self.exes.contains(&normalize_exe(exe)) {
+            return true;
+        }
+        if self.patterns.iter().any(|re| re.is_match(title)) {
+            return true;
+        }
+        if self.incognito {
+            let lower = title.to_lowercase();
+            return INCOGNITO_MARKERS.iter().any(|m| lower.contains(m));
+        }
+        false
+    }
+
+    /// 对一个窗口做脱敏，返回 None 表示应丢弃
+    pub fn redact(&self, title: String, exe: String) -> Option<(String, String)> {
+        if title == PRIVATE_TITLE || !self.is_excluded(&title, &exe) {
+            return Some((title, exe));
+        }
+        match self.mode {
+            PrivacyMode::Drop => None,
+            PrivacyMode::Private => Some((PRIVATE_TITLE.to_string(), String::new())),
+            PrivacyMode::Hash => Some((hash_title(&title), exe)),
+        }
+    }
+
+    /// 对已记录的活动做脱敏，用于构造发给 AI 的提示词（规则生效前记下的标题也会被处理）
+    pub fn redact_entries(&self, entries: Vec<ActivityEntry>) -> Vec<ActivityEntry> {
+        entries
+            .into_iter()
+            .filter_map(|mut e| {
+                if e.afk {
+                    return Some(e);
+                }
+                if !self.is_excluded(&e.title, &e.exe) {
+                    return Some(e);
+                }
+                let (title, exe) = self.redact(std::mem::take(&mut e.title), std::mem::take(&mut e.exe))?;
+                // 域名同样可能泄露隐私
+                e.domain = None;
+                e.title = title;
+                e.exe = exe;
+                Some(e)
+            })
+            .collect()
+    }
+}
+
+fn hash_title(title: &str) -> String {
+    let digest = Sha256::digest(title.as_bytes());
+    let hex: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    format!("#{}", hex)
+}
+
+/// 校验标题正则
+pub fn validate(cfg: &PrivacyConfig) -> Result<(), String> {
+    for p in &cfg.exclude_title_patterns {
+        Regex::new(p).map_err(|e| format!("隐私规则正则无效 {}: {}", p, e))?;
+    }
+    Ok(())
+}
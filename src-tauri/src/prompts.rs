@@ -0,0 +1,466 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// 渲染模板用的变量
+pub type Vars = BTreeMap<String, String>;
+
+pub fn vars<const N: usize>(pairs: [(&str, String); N]) -> Vars {
+    pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+/// 内置模板：system 为系统提示，user 为用户消息，{变量} 在调用时替换
+struct DefaultPrompt {
+    name: &'static str,
+    title: &'static str,
+    /// 所属功能（ai_client::Feature 的键）
+    feature: &'static str,
+    system: &'static str,
+    user: &'static str,
+    /// (变量名, 说明, 预览用的示例值)
+    variables: &'static [(&'static str, &'static str, &'static str)],
+}
+
+const PERSONA: (&str, &str, &str) = ("persona", "当前小鸟性格的人设描述", "你是一只活泼的小蓝鸟。");
+const LANGUAGE: (&str, &str, &str) = ("language_instruction", "按语言设置生成的输出语言要求", "请使用中文写日记。");
+
+const DEFAULTS: &[DefaultPrompt] = &[
+    DefaultPrompt {
+        name: "diary_generate",
+        title: "日记生成",
+        feature: "diary",
+        system: concat!(
+            "{persona}\n",
+            "你是一个日记助手。根据用户提供的电脑活动数据和备注，写一篇自然的日记。\n",
+            "要求：\n",
+            "- 第一人称\n",
+            "- 按时间/事件顺序组织\n",
+            "- 润色总结，不是流水账。提炼、归纳，合并同类事项\n",
+            "- 技术细节适当简化，记录做了什么和结果如何\n",
+            "- 保留情绪和感受\n",
+            "- 语气自然口语化\n",
+            "- 格式：# YYYY-MM-DD 星期X 开头\n",
+            "- 如果是双语，中文在上，英文在下，用 --- 分隔\n",
+            "- 末尾加 *记录时间：HH:MM*",
+        ),
+        user: "请根据以下信息，为 {date} {weekday} 写一篇日记。\n\n{activity_summary}{notes}{language_instruction}",
+        variables: &[
+            PERSONA,
+            ("date", "日期 YYYY-MM-DD", "2026-03-01"),
+            ("weekday", "星期", "周日"),
+            ("activity_summary", "当天分类统计和最近 30 条活动明细（已按隐私规则脱敏）", "今日活动统计：\n- 编程: 3h 20m\n\n"),
+            ("notes", "用户手动备注", "用户手动备注：\n下午去买了咖啡\n\n"),
+            LANGUAGE,
+        ],
+    },
+    DefaultPrompt {
+        name: "diary_organize",
+        title: "日记整理",
+        feature: "diary",
+        system: concat!(
+            "{persona}\n",
+            "你是一个日记整理助手。用户会给你一段日记内容，可能包含：已有的完整日记、用户新加的笔记、AI生成的片段等。\n",
+            "你的任务是将它们整合成一篇连贯自然的日记。\n",
+            "核心原则：\n",
+            "- 绝对不能丢失任何用户写的内容，每一句用户新增的话都必须体现在最终结果中\n",
+            "- 用户新加的笔记要融入到日记的合适位置，不能忽略或吞掉\n",
+            "- 如果原文已经很完整，只需要把新增内容自然地插入到对应位置即可，不要大幅重写\n",
+            "格式要求：\n",
+            "- 第一人称\n",
+            "- 按时间/事件顺序组织\n",
+            "- 语气自然口语化\n",
+            "- 格式：# YYYY-MM-DD 星期X 开头\n",
+            "- 如果是双语，中文在上，英文在下，用 --- 分隔，新增内容两个语言版本都要加\n",
+            "- 末尾加 *记录时间：HH:MM*",
+        ),
+        user: "请将以下 {date} {weekday} 的日记内容整理成一篇连贯的日记：\n\n{raw_content}\n\n{language_instruction}",
+        variables: &[
+            PERSONA,
+            ("date", "日期 YYYY-MM-DD", "2026-03-01"),
+            ("weekday", "星期", "周日"),
+            ("raw_content", "待整理的日记原文", "# 2026-03-01 周日\n上午写代码。\n补充：晚上看了电影"),
+            LANGUAGE,
+        ],
+    },
+    DefaultPrompt {
+        name: "report",
+        title: "周报 / 月报",
+        feature: "diary",
+        system: concat!(
+            "{persona}\n",
+            "你是一个{report_type}助手。根据用户提供的活动数据和日记摘要，生成一份简洁的{report_type}。\n",
+            "要求：总结主要活动和成果、分析时间分配、提出简短改进建议、语气轻松友好、Markdown 格式、300 字以内。",
+        ),
+        user: "请根据以下数据生成一份{report_type}（{period_label}）。\n\n{daily_totals}{category_summary}{journal_snippet}{language_instruction}",
+        variables: &[
+            PERSONA,
+            ("report_type", "周报或月报", "周报"),
+            ("period_label", "时间段描述", "最近 7 天"),
+            ("daily_totals", "每日活动时长", "每日活动时长：\n- 2026-03-01: 5h 10m\n\n"),
+            ("category_summary", "分类汇总", "分类汇总：\n- 编程: 12h\n\n"),
+            ("journal_snippet", "每天日记的前 200 字", "日记摘要：\n【2026-03-01】今天修好了一个 bug……\n\n"),
+            ("language_instruction", "输出语言要求", "请使用中文。"),
+        ],
+    },
+    DefaultPrompt {
+        name: "theater",
+        title: "记忆小剧场",
+        feature: "theater",
+        system: concat!(
+            "{persona}\n",
+            "你是一个轻小说风格的叙事者。根据用户的一天活动和日记，生成一篇有趣的「一日番外」。\n",
+            "要求：\n",
+            "- 第三人称视角，主角是「主人」\n",
+            "- 有场景描写、事件推进、情绪旁白\n",
+            "- 语气轻松有趣，像轻小说\n",
+            "- 300-500字正文\n",
+            "- 正文结束后，用 --- 分隔，给出2-3个「如果」分支，格式：\n",
+            "## 如果xxx\n",
+            "（50-100字的平行世界短文）\n",
+            "- 用中文",
+        ),
+        user: "请为 {date} 生成一篇轻小说风格的「一日番外」。\n\n{activity_summary}{journal_snippet}",
+        variables: &[
+            PERSONA,
+            ("date", "日期 YYYY-MM-DD", "2026-03-01"),
+            ("activity_summary", "当天各分类的分钟数", "今日活动：\n- 编程: 200分钟\n\n"),
+            ("journal_snippet", "当天日记的前 500 字", "今日日记摘要：\n今天修好了一个 bug……\n\n"),
+        ],
+    },
+    DefaultPrompt {
+        name: "vocab",
+        title: "单词例句",
+        feature: "vocab",
+        system: concat!(
+            "你是一个英语学习助手。为每个英文单词生成3种风格的例句。\n",
+            "输出 JSON 数组格式，每个元素：{\"word\":\"xxx\",\"examples\":[{\"style\":\"日常\",\"text\":\"...\"},{\"style\":\"网文\",\"text\":\"...\"},{\"style\":\"程序员\",\"text\":\"...\"}]}\n",
+            "网文风格要有中二感，程序员风格要有吐槽感。只输出 JSON，不要其他内容。",
+        ),
+        user: "请为以下单词生成例句：\n{word_list}",
+        variables: &[("word_list", "单词及来源窗口，每行一个", "- refactor (来源: Visual Studio Code)\n")],
+    },
+    DefaultPrompt {
+        name: "alchemy",
+        title: "灵感炼金",
+        feature: "alchemy",
+        system: concat!(
+            "{persona}\n",
+            "你是一个灵感炼金师。将用户提供的零散灵感碎片合成为一段完整的、可用的创作素材。\n",
+            "要求：\n",
+            "- 找出灵感之间的隐藏关联\n",
+            "- 合成一个有趣的设定/场景/段落（200-400字）\n",
+            "- 给合成结果起一个标题\n",
+            "- 输出格式：第一行是标题，空一行后是正文\n",
+            "- 用中文",
+        ),
+        user: "请将以下灵感碎片炼金合成（请给出全新的、不同于之前的创意角度，seed={seed}）：\n{material}",
+        variables: &[
+            PERSONA,
+            ("seed", "随机种子（毫秒时间戳），让每次结果不同", "1772323200000"),
+            ("material", "选中的灵感，每行一条", "- [1] 会说话的路灯 (情绪: 好奇, 标签: 奇幻)\n- [2] 雨夜的末班车 (情绪: 孤独, 标签: 城市)\n"),
+        ],
+    },
+    DefaultPrompt {
+        name: "writing_suggest",
+        title: "续写建议",
+        feature: "writing",
+        system: concat!(
+            "你是一个小说写作助手。根据以下上下文，给出3个不同的后续发展方向建议。\n",
+            "每个建议用一句话概括方向，再用2-3句话描述具体可以怎么写。\n",
+            "不要直接写正文，只给思路。\n\n",
+            "{characters}",
+        ),
+        user: "当前内容：\n{context}",
+        variables: &[
+            ("characters", "已知人物（名字和身份），没有人物时为空", "已知人物：\n- 林夏（主角）\n\n"),
+            ("context", "光标前的最后一段", "雨停了，林夏推开门。"),
+        ],
+    },
+    DefaultPrompt {
+        name: "consistency_check",
+        title: "一致性检查",
+        feature: "writing",
+        system: concat!(
+            "你是一个小说校对助手。对照以下设定资料，检查这章内容有没有矛盾或不一致的地方。\n",
+            "如果发现矛盾，指出具体位置和原因。如果没有问题，说\"未发现矛盾\"。",
+        ),
+        user: "设定资料：\n{worldbuilding}\n\n人物档案：\n{characters}\n\n本章内容：\n{chapter}",
+        variables: &[
+            ("worldbuilding", "设定集 JSON（前 2000 字）", "{\"世界观\": \"近未来海滨小城\"}"),
+            ("characters", "人物档案：名字、身份、性格", "- 林夏：主角 / 外冷内热\n"),
+            ("chapter", "本章正文", "林夏推开门，外面下着雪。"),
+        ],
+    },
+    DefaultPrompt {
+        name: "foreshadow_detect",
+        title: "伏笔检测",
+        feature: "writing",
+        system: concat!(
+            "你是一个小说分析助手。阅读以下章节内容，找出可能是伏笔的地方（暗示、悬念、未解释的细节）。\n",
+            "列出你发现的潜在伏笔，以及是否可能回收了已有伏笔。",
+        ),
+        user: "已知伏笔列表：\n{foreshadows}\n\n本章内容：\n{chapter}",
+        variables: &[
+            ("foreshadows", "已登记的伏笔及回收状态", "- [未回收] fs1：抽屉里的旧钥匙\n"),
+            ("chapter", "本章正文", "她把钥匙放回了抽屉。"),
+        ],
+    },
+    DefaultPrompt {
+        name: "chapter_summary",
+        title: "章节摘要",
+        feature: "writing",
+        system: "你是一个小说摘要助手。用一句话概括这章的核心内容（不超过50字）。只输出摘要，不要其他内容。",
+        user: "{chapter}",
+        variables: &[("chapter", "本章正文", "林夏回到海边小城，发现旧友失踪。")],
+    },
+    DefaultPrompt {
+        name: "dream_analysis",
+        title: "解梦",
+        feature: "dream",
+        system: "你是一位温柔的梦境分析师。请分析以下梦境，从心理学角度给出简短解读（3-5句话），语气温暖有趣，不要太严肃。",
+        user: "梦境标题：{title}\n梦境内容：{content}\n情绪：{mood}\n是否清醒梦：{lucid}",
+        variables: &[
+            ("title", "梦境标题", "会飞的图书馆"),
+            ("content", "梦境内容", "我在一座漂浮的图书馆里找一本没有名字的书。"),
+            ("mood", "情绪标签", "奇妙"),
+            ("lucid", "是否清醒梦（是/否）", "否"),
+        ],
+    },
+];
+
+/// 用户改过的模板，只保存与内置不同的部分
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PromptOverride {
+    system: String,
+    user: String,
+}
+
+/// 列表和编辑用的模板信息
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptInfo {
+    pub name: String,
+    pub title: String,
+    pub feature: String,
+    pub system: String,
+    pub user: String,
+    pub variables: Vec<PromptVariable>,
+    /// 是否已被修改（可恢复默认）
+    pub customized: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptVariable {
+    pub name: String,
+    pub description: String,
+}
+
+/// 渲染后的提示词；prompts.json 无法解析时用的是内置模板，warning 说明原因
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedPrompt {
+    pub system: String,
+    pub user: String,
+    pub warning: Option<String>,
+}
+
+/// 模板列表；error 为 prompts.json 的解析错误，此时列出的是内置模板
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptList {
+    pub prompts: Vec<PromptInfo>,
+    pub error: Option<String>,
+}
+
+fn prompts_path(data_dir: &PathBuf) -> PathBuf {
+    data_dir.join("prompts.json")
+}
+
+/// 读取用户修改；文件不存在时为空，读取或解析失败时返回错误
+fn load_overrides(data_dir: &PathBuf) -> Result<BTreeMap<String, PromptOverride>, String> {
+    let content = match fs::read_to_string(prompts_path(data_dir)) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(e) => return Err(format!("读取 prompts.json 失败: {}", e)),
+    };
+    serde_json::from_str(&content).map_err(|e| format!("prompts.json 解析失败: {}", e))
+}
+
+/// 只读场景：解析失败时退回内置模板，并带上错误
+fn overrides_or_default(data_dir: &PathBuf) -> (BTreeMap<String, PromptOverride>, Option<String>) {
+    match load_overrides(data_dir) {
+        Ok(overrides) => (overrides, None),
+        Err(e) => (BTreeMap::new(), Some(e)),
+    }
+}
+
+/// 改写前读取：文件无法解析时拒绝，以免只含一个模板的新文件覆盖其他修改
+fn overrides_for_write(data_dir: &PathBuf) -> Result<BTreeMap<String, PromptOverride>, String> {
+    load_overrides(data_dir).map_err(|e| format!("{}，请先手动修正或删除 prompts.json 再保存", e))
+}
+
+fn save_overrides(data_dir: &PathBuf, overrides: &BTreeMap<String, PromptOverride>) -> Result<(), String> {
+    fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(overrides).map_err(|e| e.to_string())?;
+    fs::write(prompts_path(data_dir), json).map_err(|e| e.to_string())
+}
+
+fn find(name: &str) -> Result<&'static DefaultPrompt, String> {
+    DEFAULTS.iter().find(|d| d.name == name).ok_or_else(|| format!("没有名为 {} 的提示词模板", name))
+}
+
+fn info(d: &DefaultPrompt, custom: Option<&PromptOverride>) -> PromptInfo {
+    PromptInfo {
+        name: d.name.to_string(),
+        title: d.title.to_string(),
+        feature: d.feature.to_string(),
+        system: custom.map(|c| c.system.clone()).unwrap_or_else(|| d.system.to_string()),
+        user: custom.map(|c| c.user.clone()).unwrap_or_else(|| d.user.to_string()),
+        variables: d
+            .variables
+            .iter()
+            .map(|(name, desc, _)| PromptVariable { name: name.to_string(), description: desc.to_string() })
+            .collect(),
+        customized: custom.is_some(),
+    }
+}
+
+/// 所有模板（已合并用户修改）
+pub fn list(data_dir: &PathBuf) -> PromptList {
+    let (overrides, error) = overrides_or_default(data_dir);
+    PromptList { prompts: DEFAULTS.iter().map(|d| info(d, overrides.get(d.name))).collect(), error }
+}
+
+pub fn get(data_dir: &PathBuf, name: &str) -> Result<PromptInfo, String> {
+    let d = find(name)?;
+    Ok(info(d, overrides_or_default(data_dir).0.get(name)))
+}
+
+/// 保存修改；引用了模板不支持的变量时拒绝，与默认相同则视为恢复默认
+pub fn save(data_dir: &PathBuf, name: &str, system: &str, user: &str) -> Result<PromptInfo, String> {
+    let d = find(name)?;
+    let unknown: Vec<String> = placeholders(system)
+        .into_iter()
+        .chain(placeholders(user))
+        .filter(|p| !d.variables.iter().any(|(v, _, _)| v == p))
+        .collect();
+    if !unknown.is_empty() {
+        return Err(format!("模板 {} 不支持变量：{}", d.title, unknown.join("、")));
+    }
+    let mut overrides = overrides_for_write(data_dir)?;
+    if system == d.system && user == d.user {
+        overrides.remove(name);
+    } else {
+        overrides.insert(name.to_string(), PromptOverride { system: system.to_string(), user: user.to_string() });
+    }
+    save_overrides(data_dir, &overrides)?;
+    Ok(info(d, overrides.get(name)))
+}
+
+/// 恢复内置模板
+pub fn reset(data_dir: &PathBuf, name: &str) -> Result<PromptInfo, String> {
+    let d = find(name)?;
+    let mut overrides = overrides_for_write(data_dir)?;
+    if overrides.remove(name).is_some() {
+        save_overrides(data_dir, &overrides)?;
+    }
+    Ok(info(d, None))
+}
+
+/// 用变量渲染模板，得到发给 AI 的系统提示和用户消息；prompts.json 无法解析时用内置模板
+pub fn render(data_dir: &PathBuf, name: &str, vars: &Vars) -> Result<RenderedPrompt, String> {
+    let d = find(name)?;
+    let (overrides, warning) = overrides_or_default(data_dir);
+    let p = info(d, overrides.get(name));
+    Ok(RenderedPrompt { system: substitute(&p.system, vars), user: substitute(&p.user, vars), warning })
+}
+
+/// 预览：没给出的变量用示例值补上
+pub fn preview(data_dir: &PathBuf, name: &str, mut vars: Vars) -> Result<RenderedPrompt, String> {
+    let d = find(name)?;
+    for (var, _, sample) in d.variables {
+        vars.entry(var.to_string()).or_insert_with(|| sample.to_string());
+    }
+    render(data_dir, name, &vars)
+}
+
+/// 模板中出现的 {变量名}（小写字母和下划线），JSON 示例里的花括号不算
+fn placeholders(template: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('}') else { break };
+        let name = &rest[..end];
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+            out.push(name.to_string());
+            rest = &rest[end + 1..];
+        }
+    }
+    out
+}
+
+/// 替换已知变量，未知的 {xxx} 原样保留
+pub fn substitute(template: &str, vars: &Vars) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(end) = after.find('}') {
+            if let Some(value) = vars.get(&after[..end]) {
+                out.push_str(value);
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        out.push('{');
+        rest = after;
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xiaolanniao-prompts-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn broken_file_falls_back_to_defaults_and_blocks_writes() {
+        let dir = temp_dir("broken");
+        let broken = "{\"diary_generate\": {\"system\": \"我的系统提示\"";
+        fs::write(prompts_path(&dir), broken).unwrap();
+
+        let list = list(&dir);
+        assert!(list.error.as_deref().unwrap_or("").contains("prompts.json"));
+        assert!(list.prompts.iter().all(|p| !p.customized));
+        let rendered = preview(&dir, "vocab", Vars::new()).unwrap();
+        assert!(rendered.warning.is_some());
+
+        let d = find("vocab").unwrap();
+        assert!(save(&dir, "vocab", "新的系统提示", d.user).is_err());
+        assert!(reset(&dir, "diary_generate").is_err());
+        assert_eq!(fs::read_to_string(prompts_path(&dir)).unwrap(), broken);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn save_keeps_other_overrides() {
+        let dir = temp_dir("save");
+        let vocab = find("vocab").unwrap();
+        let theater = find("theater").unwrap();
+        save(&dir, "vocab", "改过的单词提示", vocab.user).unwrap();
+        save(&dir, "theater", "改过的小剧场提示", theater.user).unwrap();
+
+        let list = list(&dir);
+        assert!(list.error.is_none());
+        assert_eq!(list.prompts.iter().filter(|p| p.customized).count(), 2);
+        assert_eq!(get(&dir, "vocab").unwrap().system, "改过的单词提示");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
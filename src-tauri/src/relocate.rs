@@ -0,0 +1,203 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+/// 迁移进行中时置位，追踪器据此拒绝启动
+pub static MOVING: AtomicBool = AtomicBool::new(false);
+
+/// 迁移进度，phase 为 copy / verify / cleanup
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveProgress {
+    pub phase: &'static str,
+    pub done: u64,
+    pub total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveReport {
+    pub files: u64,
+    pub bytes: u64,
+    /// 旧目录中留下的文件数：删不掉的、复制后又被改过的、复制后才新建的（新目录已可用，可手动核对）
+    pub left_behind: u64,
+    /// 留下的文件（相对旧目录）
+    pub left_files: Vec<String>,
+    /// 已复制并校验过的文件（相对路径），只有这些会从旧目录删除
+    #[serde(skip)]
+    pub copied: Vec<PathBuf>,
+    /// 复制前目标目录已存在（空目录），撤销时保留
+    #[serde(skip)]
+    pub target_existed: bool,
+}
+
+/// 递归列出目录下所有文件（相对路径, 大小）
+fn list_files(root: &Path, dir: &Path, out: &mut Vec<(PathBuf, u64)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            list_files(root, &path, out)?;
+        } else {
+            let rel = path.strip_prefix(root).map(|p| p.to_path_buf()).unwrap_or_default();
+            out.push((rel, entry.metadata()?.len()));
+        }
+    }
+    Ok(())
+}
+
+/// 规范化路径：取最近一级已存在的上级目录做 canonicalize，再接上不存在的部分
+fn absolute(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+    let mut abs = fs::canonicalize(existing).unwrap_or_else(|_| existing.to_path_buf());
+    abs.extend(rest.iter().rev());
+    abs
+}
+
+fn file_hash(path: &Path) -> io::Result<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// 把 from 整个目录复制到 to 并逐个校验，成功后由调用方更新配置，再调用 remove_old 删除旧目录
+///
+/// to 必须不存在或为空目录；任何一步失败都会删除已复制的内容，旧目录保持不动
+pub fn copy_tree<F>(from: &Path, to: &Path, on_progress: F) -> Result<MoveReport, String>
+where
+    F: Fn(MoveProgress),
+{
+    let (from_abs, to_abs) = (absolute(from), absolute(to));
+    if to_abs == from_abs {
+        return Err("新目录与当前数据目录相同".into());
+    }
+    // 目标在旧目录里面会把自己也复制进去
+    if to_abs.starts_with(&from_abs) {
+        return Err("新目录不能位于当前数据目录之内".into());
+    }
+    if to.exists() && fs::read_dir(to).map_err(|e| e.to_string())?.next().is_some() {
+        return Err("目标目录不为空".into());
+    }
+
+    let mut files = Vec::new();
+    if from.exists() {
+        list_files(from, from, &mut files).map_err(|e| format!("读取数据目录失败: {}", e))?;
+    }
+    let total = files.len() as u64;
+    let bytes_total: u64 = files.iter().map(|(_, len)| len).sum();
+
+    let existed = to.exists();
+    let result = copy_and_verify(from, to, &files, bytes_total, &on_progress);
+    let mut report = MoveReport {
+        files: total,
+        bytes: bytes_total,
+        left_behind: 0,
+        left_files: vec![],
+        copied: files.into_iter().map(|(rel, _)| rel).collect(),
+        target_existed: existed,
+    };
+    if let Err(e) = result {
+        // 回滚：只删本次复制的文件，目标目录原本存在时保留
+        report.copied.retain(|rel| to.join(rel).exists());
+        undo_copy(to, &report);
+        return Err(e);
+    }
+    Ok(report)
+}
+
+/// 撤销 copy_tree：删除复制到 to 的文件和因此新建的空目录
+pub fn undo_copy(to: &Path, report: &MoveReport) {
+    for rel in &report.copied {
+        let _ = fs::remove_file(to.join(rel));
+    }
+    prune_empty_dirs(to, !report.target_existed);
+}
+
+/// 自底向上删除 root 下的空目录，remove_root 时 root 为空也一并删除
+fn prune_empty_dirs(root: &Path, remove_root: bool) {
+    if let Ok(entries) = fs::read_dir(root) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                prune_empty_dirs(&entry.path(), true);
+            }
+        }
+    }
+    if remove_root {
+        // 不为空时 remove_dir 会失败，正好保留
+        let _ = fs::remove_dir(root);
+    }
+}
+
+fn copy_and_verify<F>(from: &Path, to: &Path, files: &[(PathBuf, u64)], bytes_total: u64, on_progress: &F) -> Result<(), String>
+where
+    F: Fn(MoveProgress),
+{
+    let total = files.len() as u64;
+    fs::create_dir_all(to).map_err(|e| format!("创建目录失败: {}", e))?;
+
+    let mut bytes_done = 0;
+    for (i, (rel, len)) in files.iter().enumerate() {
+        let dest = to.join(rel);
+        if let Some(dir) = dest.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("创建目录失败 {}: {}", dir.display(), e))?;
+        }
+        fs::copy(from.join(rel), &dest).map_err(|e| format!("复制 {} 失败: {}", rel.display(), e))?;
+        bytes_done += len;
+        on_progress(MoveProgress { phase: "copy", done: i as u64 + 1, total, bytes_done, bytes_total });
+    }
+
+    let mut bytes_done = 0;
+    for (i, (rel, len)) in files.iter().enumerate() {
+        let src = file_hash(&from.join(rel)).map_err(|e| format!("校验 {} 失败: {}", rel.display(), e))?;
+        let dst = file_hash(&to.join(rel)).map_err(|e| format!("校验 {} 失败: {}", rel.display(), e))?;
+        if src != dst {
+            return Err(format!("校验不一致: {}", rel.display()));
+        }
+        bytes_done += len;
+        on_progress(MoveProgress { phase: "verify", done: i as u64 + 1, total, bytes_done, bytes_total });
+    }
+    Ok(())
+}
+
+/// 删除旧目录中已复制并校验过的文件，其余文件原样保留并记入 report.left_files
+///
+/// 迁移期间其他功能（日记、AI 用量等）仍可能写入旧目录：复制之后才新建的文件不在 copied 中，
+/// 复制后又被改过的文件与新目录中的副本不一致，这两种都不删除
+pub fn remove_old<F>(from: &Path, to: &Path, report: &mut MoveReport, on_progress: F)
+where
+    F: Fn(MoveProgress),
+{
+    let total = report.copied.len() as u64;
+    for (i, rel) in report.copied.iter().enumerate() {
+        let (old, new) = (from.join(rel), to.join(rel));
+        let same = matches!((file_hash(&old), file_hash(&new)), (Ok(a), Ok(b)) if a == b);
+        if !same || fs::remove_file(&old).is_err() {
+            report.left_files.push(rel.display().to_string());
+        }
+        on_progress(MoveProgress { phase: "cleanup", done: i as u64 + 1, total, bytes_done: 0, bytes_total: 0 });
+    }
+    let mut rest = Vec::new();
+    if list_files(from, from, &mut rest).is_ok() {
+        for (rel, _) in rest {
+            let rel = rel.display().to_string();
+            if !report.left_files.contains(&rel) {
+                report.left_files.push(rel);
+            }
+        }
+    }
+    report.left_behind = report.left_files.len() as u64;
+    prune_empty_dirs(from, true);
+}
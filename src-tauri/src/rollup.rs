@@ -0,0 +1,220 @@
+use crate::activity;
+use crate::categories;
+use crate::edits;
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 源文件指纹：JSONL 和手动修改文件的大小、修改时间，以及分类规则的修改时间，任一变化即重算
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+struct Stamp {
+    len: u64,
+    mtime_ms: u64,
+    #[serde(default)]
+    edits_len: u64,
+    #[serde(default)]
+    edits_ms: u64,
+    rules_ms: u64,
+}
+
+/// 缓存格式版本，字段变化时加一让旧缓存失效
+const ROLLUP_VERSION: u32 = 3;
+
+/// 单日活动汇总
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DayRollup {
+    pub date: String,
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    stamp: Stamp,
+    /// 当天记录条数（含离开记录）
+    pub entries: u32,
+    /// 活动总秒数，不含离开
+    pub active_sec: u64,
+    /// 按分类汇总（同 activity::summarize：不含离开和 30 秒以下的记录），按时长降序
+    pub by_category: Vec<(String, u64)>,
+    /// 按进程名（小写）汇总，口径同 by_category
+    pub by_exe: HashMap<String, u64>,
+    /// 按小时汇总的活动秒数（24 项），跨整点的记录拆到各小时
+    pub by_hour: Vec<u64>,
+    /// 每个分类的按小时秒数（含 30 秒以下的记录）
+    #[serde(default)]
+    pub category_hours: HashMap<String, Vec<u64>>,
+    /// 每个进程名（小写）的按小时秒数
+    #[serde(default)]
+    pub exe_hours: HashMap<String, Vec<u64>>,
+    /// 是否有深夜（23 点到 4 点开始的）活动
+    pub late_night: bool,
+}
+
+impl DayRollup {
+    pub fn category_sec(&self, category: &str) -> u64 {
+        self.by_category.iter().find(|(c, _)| c == category).map(|(_, s)| *s).unwrap_or(0)
+    }
+}
+
+fn millis(t: Option<SystemTime>) -> u64 {
+    t.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn stamp_of(data_dir: &PathBuf, date: &str) -> Option<Stamp> {
+    let meta = fs::metadata(data_dir.join("activity").join(format!("{}.jsonl", date))).ok();
+    let edits = fs::metadata(edits::edits_path(data_dir, date)).ok();
+    // 只有手动补录、没有原始记录的日子也要汇总
+    if meta.is_none() && edits.is_none() {
+        return None;
+    }
+    let rules = fs::metadata(data_dir.join("categories.json")).and_then(|m| m.modified()).ok();
+    Some(Stamp {
+        len: meta.as_ref().map(|m| m.len()).unwrap_or(0),
+        mtime_ms: millis(meta.and_then(|m| m.modified().ok())),
+        edits_len: edits.as_ref().map(|m| m.len()).unwrap_or(0),
+        edits_ms: millis(edits.and_then(|m| m.modified().ok())),
+        rules_ms: millis(rules),
+    })
+}
+
+fn rollup_path(data_dir: &PathBuf, date: &str) -> PathBuf {
+    data_dir.join("rollup").join(format!("{}.json", date))
+}
+
+/// 从 JSONL 重算一天的汇总
+fn compute(data_dir: &PathBuf, date: &str, stamp: Stamp) -> DayRollup {
+    let entries = activity::load_entries(data_dir, date);
+    let rules = categories::load(data_dir);
+    let mut by_exe: HashMap<String, u64> = HashMap::new();
+    let mut by_hour = vec![0u64; 24];
+    let mut category_hours: HashMap<String, Vec<u64>> = HashMap::new();
+    let mut exe_hours: HashMap<String, Vec<u64>> = HashMap::new();
+    let mut late_night = false;
+
+    for e in &entries {
+        if e.afk {
+            continue;
+        }
+        let start = match NaiveDateTime::parse_from_str(&e.ts, "%Y-%m-%dT%H:%M:%S") {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        let hour = start.hour();
+        if !(4..23).contains(&hour) {
+            late_night = true;
+        }
+        if e.duration >= 30 {
+            *by_exe.entry(e.exe.to_lowercase()).or_insert(0) += e.duration;
+        }
+        let cat_hours = category_hours
+            .entry(activity::category_of(&rules, e))
+            .or_insert_with(|| vec![0; 24]);
+        let exe_hours = exe_hours.entry(e.exe.to_lowercase()).or_insert_with(|| vec![0; 24]);
+        for (h, sec) in split_hours(hour, start.minute() * 60 + start.second(), e.duration) {
+            by_hour[h] += sec;
+            cat_hours[h] += sec;
+            exe_hours[h] += sec;
+        }
+    }
+
+    DayRollup {
+        date: date.to_string(),
+        version: ROLLUP_VERSION,
+        stamp,
+        entries: entries.len() as u32,
+        active_sec: activity::active_seconds(&entries),
+        by_category: activity::summarize(data_dir, &entries),
+        by_exe,
+        by_hour,
+        category_hours,
+        exe_hours,
+        late_night,
+    }
+}
+
+/// 把从 hour 点 offset 秒开始、持续 duration 秒的记录按整点拆成 (小时, 秒数)
+///
+/// 超过当天 24 点的部分丢弃（属于第二天）
+fn split_hours(hour: u32, offset: u32, duration: u64) -> Vec<(usize, u64)> {
+    let mut parts = Vec::new();
+    let mut offset = offset as u64;
+    let mut left = duration;
+    let mut h = hour as usize;
+    while left > 0 && h < 24 {
+        let chunk = left.min(3600 - offset);
+        parts.push((h, chunk));
+        left -= chunk;
+        offset = 0;
+        h += 1;
+    }
+    parts
+}
+
+/// 内存缓存，键为 (数据目录, 日期)
+static CACHE: Mutex<Option<HashMap<(PathBuf, String), DayRollup>>> = Mutex::new(None);
+
+/// 获取一天的汇总：先查内存，再查 rollup/ 下的缓存文件，指纹不符时重算并写回
+pub fn day(data_dir: &PathBuf, date: &str) -> DayRollup {
+    let stamp = match stamp_of(data_dir, date) {
+        Some(s) => s,
+        None => {
+            return DayRollup { date: date.to_string(), by_hour: vec![0; 24], ..Default::default() };
+        }
+    };
+    let key = (data_dir.clone(), date.to_string());
+
+    if let Some(r) = CACHE.lock().unwrap_or_else(|e| e.into_inner()).as_ref().and_then(|m| m.get(&key)) {
+        if r.stamp == stamp {
+            return r.clone();
+        }
+    }
+
+    let path = rollup_path(data_dir, date);
+    let cached = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<DayRollup>(&s).ok())
+        .filter(|r| r.version == ROLLUP_VERSION && r.stamp == stamp);
+    let rollup = match cached {
+        Some(r) => r,
+        None => {
+            let r = compute(data_dir, date, stamp);
+            if let Ok(json) = serde_json::to_string(&r) {
+                let _ = fs::create_dir_all(data_dir.join("rollup"));
+                let _ = fs::write(&path, json);
+            }
+            r
+        }
+    };
+
+    CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashMap::new)
+        .insert(key, rollup.clone());
+    rollup
+}
+
+/// 获取 [from, to] 闭区间内每天的汇总，按日期升序
+pub fn range(data_dir: &PathBuf, from: NaiveDate, to: NaiveDate) -> Vec<DayRollup> {
+    from.iter_days()
+        .take_while(|d| *d <= to)
+        .map(|d| day(data_dir, &d.format("%Y-%m-%d").to_string()))
+        .collect()
+}
+
+/// 合并多天的分类汇总，按时长降序
+pub fn merge_categories(days: &[DayRollup]) -> Vec<(String, u64)> {
+    let mut map: HashMap<String, u64> = HashMap::new();
+    for d in days {
+        for (cat, sec) in &d.by_category {
+            *map.entry(cat.clone()).or_insert(0) += sec;
+        }
+    }
+    let mut sorted: Vec<(String, u64)> = map.into_iter().collect();
+    sorted.sort_by_key(|(_, sec)| std::cmp::Reverse(*sec));
+    sorted
+}
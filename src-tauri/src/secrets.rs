@@ -0,0 +1,227 @@
+use crate::config::{self, AppConfig};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 系统钥匙串中的服务名
+const SERVICE: &str = "xiaolanniao";
+/// config.json 中的密钥引用前缀：存在系统钥匙串 / 加密文件里
+const KEYRING_PREFIX: &str = "keyring:";
+const FILE_PREFIX: &str = "secret-file:";
+/// 无界面环境可通过环境变量提供加密文件口令
+const PASSPHRASE_ENV: &str = "XIAOLANNIAO_SECRET_PASSPHRASE";
+
+/// 密钥存放位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretStore {
+    /// Linux Secret Service / Windows 凭据管理器，不可用且已解锁加密文件时退回加密文件
+    #[default]
+    Keyring,
+    /// 应用目录下用口令加密的 secrets.json
+    File,
+}
+
+pub fn is_reference(value: &str) -> bool {
+    value.starts_with(KEYRING_PREFIX) || value.starts_with(FILE_PREFIX)
+}
+
+/// 把配置里的密钥（引用或旧版明文）解析为明文
+pub fn resolve(value: &str) -> Result<String, String> {
+    if let Some(name) = value.strip_prefix(KEYRING_PREFIX) {
+        let name = name.to_string();
+        return on_own_thread(move || keyring::Entry::new(SERVICE, &name)?.get_password())
+            .map_err(|e| format!("读取系统钥匙串失败: {}", e));
+    }
+    if let Some(name) = value.strip_prefix(FILE_PREFIX) {
+        let file = load_file()?.ok_or("加密密钥文件 secrets.json 不存在，请重新填写 API Key")?;
+        let key = file_key(&file)?;
+        let sealed = file.entries.get(name).ok_or_else(|| format!("加密文件中没有密钥 {}", name))?;
+        return decrypt(&key, sealed);
+    }
+    Ok(value.to_string())
+}
+
+/// 保存一个密钥，返回写进 config.json 的引用
+fn store(store: SecretStore, name: &str, value: &str) -> Result<String, String> {
+    if store == SecretStore::Keyring {
+        let (n, v) = (name.to_string(), value.to_string());
+        match on_own_thread(move || keyring::Entry::new(SERVICE, &n)?.set_password(&v)) {
+            Ok(()) => return Ok(format!("{}{}", KEYRING_PREFIX, name)),
+            // 没有钥匙串的无界面环境：口令可用时退回加密文件
+            Err(_) if passphrase_available() => {}
+            Err(e) => return Err(format!("系统钥匙串不可用: {}（可改用加密文件存储）", e)),
+        }
+    }
+    // 只有这里会创建加密文件：沿用解锁时的盐，否则新生成
+    let mut file = match load_file()? {
+        Some(file) => file,
+        None => SecretFile { salt: unlocked_salt().unwrap_or_else(new_salt), entries: BTreeMap::new() },
+    };
+    let key = file_key(&file)?;
+    file.entries.insert(name.to_string(), encrypt(&key, value)?);
+    save_file(&file)?;
+    Ok(format!("{}{}", FILE_PREFIX, name))
+}
+
+/// 把配置中的明文密钥转存到密钥存储并替换为引用，返回是否有改动
+pub fn seal_config(cfg: &mut AppConfig) -> Result<bool, String> {
+    let mut changed = false;
+    for p in cfg.ai_providers.iter_mut() {
+        if !p.api_key.is_empty() && !is_reference(&p.api_key) {
+            p.api_key = store(cfg.secret_store, &format!("provider.{}", p.id), &p.api_key)?;
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+/// 启动时迁移 config.json 里残留的明文密钥（在版本迁移之后运行，旧版 api_key 已并入引擎列表）；存储不可用时保持原样，下次再试
+pub fn migrate_plaintext() -> Result<(), String> {
+    let mut cfg = config::load_config();
+    let changed = seal_config(&mut cfg).map_err(|e| format!("明文密钥迁移失败，仍保存在 config.json 中: {}", e))?;
+    if changed {
+        config::save_config_to_file(&cfg).map_err(|e| format!("保存迁移后的配置失败: {}", e))?;
+    }
+    Ok(())
+}
+
+/// keyring 的 Secret Service 后端内部会阻塞等待 D-Bus，放到独立线程里调用，避免卡在 tokio 运行时上
+fn on_own_thread<T, F>(f: F) -> Result<T, String>
+where
+    T: Send,
+    F: FnOnce() -> keyring::Result<T> + Send,
+{
+    std::thread::scope(|s| s.spawn(f).join())
+        .map_err(|_| "钥匙串调用异常退出".to_string())?
+        .map_err(|e| e.to_string())
+}
+
+// === 加密文件 ===
+
+#[derive(Serialize, Deserialize)]
+struct SecretFile {
+    /// Argon2 盐，base64
+    salt: String,
+    #[serde(default)]
+    entries: BTreeMap<String, Sealed>,
+}
+
+/// AES-256-GCM 密文，base64
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    data: String,
+}
+
+/// 已解锁的文件密钥（由口令和盐派生），只保存在内存中
+struct UnlockedKey {
+    salt: String,
+    key: [u8; 32],
+}
+
+static FILE_KEY: Mutex<Option<UnlockedKey>> = Mutex::new(None);
+
+fn secrets_path() -> PathBuf {
+    config::app_dir().join("secrets.json")
+}
+
+/// 读取加密文件，不存在时返回 None（只读，不会创建文件）
+fn load_file() -> Result<Option<SecretFile>, String> {
+    let content = match fs::read_to_string(secrets_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("读取 secrets.json 失败: {}", e)),
+    };
+    serde_json::from_str(&content).map(Some).map_err(|e| format!("secrets.json 格式错误: {}", e))
+}
+
+fn new_salt() -> String {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    B64.encode(salt)
+}
+
+/// 还没有加密文件时解锁所用的盐，第一次保存密钥时写入文件
+fn unlocked_salt() -> Option<String> {
+    FILE_KEY.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|k| k.salt.clone())
+}
+
+fn save_file(file: &SecretFile) -> Result<(), String> {
+    let path = secrets_path();
+    let json = serde_json::to_string_pretty(file).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
+
+fn passphrase_available() -> bool {
+    FILE_KEY.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+        || std::env::var(PASSPHRASE_ENV).is_ok_and(|p| !p.is_empty())
+}
+
+fn derive_key(passphrase: &str, file: &SecretFile) -> Result<[u8; 32], String> {
+    let salt = B64.decode(&file.salt).map_err(|e| format!("secrets.json 盐无效: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    // 已有密文时用第一条校验口令
+    if let Some(sealed) = file.entries.values().next() {
+        decrypt(&key, sealed).map_err(|_| "口令错误".to_string())?;
+    }
+    Ok(key)
+}
+
+/// 当前可用的文件密钥：先用已解锁的（盐须和文件一致），其次用环境变量里的口令
+fn file_key(file: &SecretFile) -> Result<[u8; 32], String> {
+    let mut cached = FILE_KEY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(unlocked) = cached.as_ref().filter(|k| k.salt == file.salt) {
+        return Ok(unlocked.key);
+    }
+    let passphrase = std::env::var(PASSPHRASE_ENV)
+        .ok()
+        .filter(|p| !p.is_empty())
+        .ok_or("加密密钥文件未解锁，请先在设置中输入口令")?;
+    let key = derive_key(&passphrase, file)?;
+    *cached = Some(UnlockedKey { salt: file.salt.clone(), key });
+    Ok(key)
+}
+
+/// 用口令解锁加密文件；还没有文件时即设定口令，文件在第一次保存密钥时才创建
+pub fn unlock(passphrase: &str) -> Result<(), String> {
+    if passphrase.is_empty() {
+        return Err("口令不能为空".into());
+    }
+    let file = load_file()?.unwrap_or_else(|| SecretFile { salt: new_salt(), entries: BTreeMap::new() });
+    let key = derive_key(passphrase, &file)?;
+    *FILE_KEY.lock().unwrap_or_else(|e| e.into_inner()) = Some(UnlockedKey { salt: file.salt, key });
+    Ok(())
+}
+
+fn encrypt(key: &[u8; 32], plaintext: &str) -> Result<Sealed, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let data = cipher.encrypt(&nonce, plaintext.as_bytes()).map_err(|_| "加密失败".to_string())?;
+    Ok(Sealed { nonce: B64.encode(nonce), data: B64.encode(data) })
+}
+
+fn decrypt(key: &[u8; 32], sealed: &Sealed) -> Result<String, String> {
+    let nonce = B64.decode(&sealed.nonce).map_err(|e| e.to_string())?;
+    let data = B64.decode(&sealed.data).map_err(|e| e.to_string())?;
+    if nonce.len() != 12 {
+        return Err("密文格式错误".into());
+    }
+    let cipher = Aes256Gcm::new(key.into());
+    let plain = cipher
+        .decrypt(Nonce::from_slice(&nonce), data.as_ref())
+        .map_err(|_| "解密失败（口令错误或文件损坏）".to_string())?;
+    String::from_utf8(plain).map_err(|e| e.to_string())
+}
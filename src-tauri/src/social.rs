@@ -1,4 +1,4 @@
-use crate::activity;
+use crate::rollup;
 use serde::Serialize;
 use std::path::PathBuf;
 
@@ -14,14 +14,10 @@ const THRESHOLD_MIN: u32 = 120;
 
 pub fn evaluate(data_dir: &PathBuf) -> SocialStatus {
     let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-    let entries = activity::load_entries(data_dir, &today);
-    let summary = activity::summarize(&entries);
+    let day = rollup::day(data_dir, &today);
 
-    let social_sec = summary.iter()
-        .find(|(c, _)| c == "社交")
-        .map(|(_, d)| *d)
-        .unwrap_or(0);
-    let total_sec: u64 = entries.iter().map(|e| e.duration).sum();
+    let social_sec = day.category_sec("社交");
+    let total_sec = day.active_sec;
 
     let social_min = (social_sec / 60) as u32;
     let total_min = (total_sec / 60) as u32;
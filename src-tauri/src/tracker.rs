@@ -1,10 +1,14 @@
-use chrono::Local;
+use crate::active_window;
+use crate::browser;
+use crate::categories;
+use crate::privacy::Privacy;
+use chrono::{DateTime, Local, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,71 +18,146 @@ pub struct ActivityEntry {
     pub exe: String,
     #[serde(default)]
     pub duration: u64,
+    /// 离开（无输入或锁屏）时段，不计入活动统计
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub afk: bool,
+    /// 写入时按 categories.json 规则得出的分类，旧记录没有该字段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// 浏览器当前标签页的域名（扩展上报或从标题推断）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
 }
 
+const TS_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// 离开记录的标题
+pub const AFK_TITLE: &str = "离开";
+
+/// 活动记录写锁：追加写和整文件重写（重新分类等）互斥
+pub static ACTIVITY_WRITE_LOCK: Mutex<()> = Mutex::new(());
+
 const IDLE_INDICATORS: &[&str] = &["lockapp", "windows default lock screen", ""];
 
-pub struct Tracker {
-    interval: u64,
-    data_dir: PathBuf,
-    stop_flag: Arc<AtomicBool>,
-    running: Arc<AtomicBool>,
+/// 前台窗口来源
+pub trait WindowSource: Send + 'static {
+    /// 返回当前前台窗口的 (标题, 进程名)；返回 None 表示来源已耗尽，追踪循环随之结束
+    fn sample(&mut self, now: DateTime<Local>) -> Option<(String, String)>;
+
+    /// 距最后一次键盘/鼠标输入的秒数，不支持时返回 None
+    fn idle_secs(&mut self) -> Option<u64> {
+        None
+    }
+
+    /// 空闲阈值变化时通知来源，按阈值注册空闲通知的后端需要重新注册
+    fn set_idle_threshold(&mut self, _secs: u64) {}
 }
 
-/// 获取当前前台窗口的标题和进程名
-fn get_active_window() -> (String, String) {
-    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
-    use windows::Win32::UI::WindowsAndMessaging::GetWindowTextW;
-    use windows::Win32::System::Threading::OpenProcess;
-    use windows::Win32::System::Threading::QueryFullProcessImageNameW;
-    use windows::Win32::System::Threading::PROCESS_NAME_FORMAT;
-    use windows::Win32::System::Threading::PROCESS_QUERY_LIMITED_INFORMATION;
-    use windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId;
-
-    unsafe {
-        let hwnd = GetForegroundWindow();
-        if hwnd.is_invalid() {
-            return (String::new(), String::new());
-        }
-
-        // 获取窗口标题
-        let mut title_buf = [0u16; 512];
-        let len = GetWindowTextW(hwnd, &mut title_buf);
-        let title = String::from_utf16_lossy(&title_buf[..len as usize]);
-
-        // 获取进程 ID
-        let mut pid = 0u32;
-        GetWindowThreadProcessId(hwnd, Some(&mut pid));
-        if pid == 0 {
-            return (title, String::new());
-        }
-
-        // 获取进程名
-        let exe = match OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) {
-            Ok(handle) => {
-                let mut name_buf = [0u16; 512];
-                let mut size = name_buf.len() as u32;
-                let ok = QueryFullProcessImageNameW(
-                    handle,
-                    PROCESS_NAME_FORMAT(0),
-                    windows::core::PWSTR(name_buf.as_mut_ptr()),
-                    &mut size,
-                );
-                let _ = windows::Win32::Foundation::CloseHandle(handle);
-                if ok.is_ok() {
-                    let full = String::from_utf16_lossy(&name_buf[..size as usize]);
-                    full.rsplit('\\').next().unwrap_or("").to_string()
-                } else {
-                    String::new()
-                }
+impl WindowSource for active_window::Backend {
+    fn sample(&mut self, _now: DateTime<Local>) -> Option<(String, String)> {
+        Some(self.active_window())
+    }
+
+    fn idle_secs(&mut self) -> Option<u64> {
+        active_window::Backend::idle_secs(self)
+    }
+
+    fn set_idle_threshold(&mut self, secs: u64) {
+        active_window::Backend::set_idle_threshold(self, secs);
+    }
+}
+
+/// 时钟抽象，追踪循环通过它取时间和等待
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> DateTime<Local>;
+    /// 等待 secs 秒，stop 置位时提前返回
+    fn sleep(&self, secs: u64, stop: &AtomicBool);
+}
+
+/// 真实系统时钟
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn sleep(&self, secs: u64, stop: &AtomicBool) {
+        // 分段 sleep，每秒检查一次 stop_flag
+        for _ in 0..secs {
+            if stop.load(Ordering::Relaxed) {
+                break;
             }
-            Err(_) => String::new(),
-        };
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+/// 手动时钟：sleep 只推进内部时间，不真正等待
+pub struct ManualClock {
+    now: Mutex<DateTime<Local>>,
+}
+
+impl ManualClock {
+    pub fn new(start: DateTime<Local>) -> Self {
+        Self { now: Mutex::new(start) }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Local> {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep(&self, secs: u64, _stop: &AtomicBool) {
+        let mut now = self.now.lock().unwrap();
+        *now += chrono::Duration::seconds(secs as i64);
+    }
+}
+
+/// 按脚本回放的窗口来源：每条样本 (标题, 进程名, 持续秒数) 依次占据前台
+pub struct ScriptedSource {
+    samples: Vec<(String, String, u64)>,
+    start: Option<DateTime<Local>>,
+}
 
-        (title, exe)
+impl ScriptedSource {
+    pub fn new(samples: Vec<(String, String, u64)>) -> Self {
+        Self { samples, start: None }
+    }
+}
+
+impl WindowSource for ScriptedSource {
+    fn sample(&mut self, now: DateTime<Local>) -> Option<(String, String)> {
+        let start = *self.start.get_or_insert(now);
+        let mut offset = (now - start).num_seconds().max(0) as u64;
+        for (title, exe, secs) in &self.samples {
+            if offset < *secs {
+                return Some((title.clone(), exe.clone()));
+            }
+            offset -= secs;
+        }
+        None
     }
 }
 
+/// 追踪循环的参数，运行中可通过 Tracker::reconfigure 更新，下一次采样生效
+#[derive(Clone)]
+struct Settings {
+    interval: u64,
+    idle_threshold: u64,
+    privacy: Privacy,
+}
+
+pub struct Tracker<S: WindowSource = active_window::Backend, C: Clock = SystemClock> {
+    settings: Arc<Mutex<Settings>>,
+    data_dir: PathBuf,
+    source: Arc<Mutex<S>>,
+    clock: Arc<C>,
+    stop_flag: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+}
+
 fn is_idle(title: &str, exe: &str) -> bool {
     if title.is_empty() && exe.is_empty() {
         return true;
@@ -100,6 +179,7 @@ fn write_entry(data_dir: &PathBuf, entry: &ActivityEntry) -> Result<(), String>
     fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
     let date = &entry.ts[..10]; // YYYY-MM-DD
     let path = dir.join(format!("{}.jsonl", date));
+    let _guard = ACTIVITY_WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
     let mut f = OpenOptions::new()
         .create(true)
         .append(true)
@@ -110,16 +190,278 @@ fn write_entry(data_dir: &PathBuf, entry: &ActivityEntry) -> Result<(), String>
     Ok(())
 }
 
+/// 按本地零点切分跨天的记录，每段归到各自日期（导入的记录也按此切分）
+pub fn split_at_midnight(entry: ActivityEntry) -> Vec<ActivityEntry> {
+    let Ok(mut cur) = NaiveDateTime::parse_from_str(&entry.ts, TS_FORMAT) else {
+        return vec![entry];
+    };
+    let mut left = entry.duration;
+    let mut parts = Vec::new();
+    loop {
+        let next_day = match cur.date().succ_opt() {
+            Some(d) => d.and_hms_opt(0, 0, 0).unwrap_or(cur),
+            None => cur,
+        };
+        let span = (next_day - cur).num_seconds().max(0) as u64;
+        if left <= span || span == 0 {
+            parts.push(ActivityEntry { ts: cur.format(TS_FORMAT).to_string(), duration: left, ..entry.clone() });
+            break;
+        }
+        parts.push(ActivityEntry { ts: cur.format(TS_FORMAT).to_string(), duration: span, ..entry.clone() });
+        left -= span;
+        cur = next_day;
+    }
+    parts
+}
+
+/// 写盘并回调，跨零点的记录拆成多条
+fn record<F>(data_dir: &PathBuf, entry: ActivityEntry, on_entry: &F)
+where
+    F: Fn(ActivityEntry),
+{
+    for part in split_at_midnight(entry) {
+        let _ = write_entry(data_dir, &part);
+        on_entry(part);
+    }
+}
+
+/// 进行中的记录
+#[derive(Clone, Serialize, Deserialize)]
+struct OpenEntry {
+    start: DateTime<Local>,
+    title: String,
+    exe: String,
+    domain: Option<String>,
+}
+
+/// 结束一条进行中的记录：计算时长、写盘并回调
+fn close_entry<F>(data_dir: &PathBuf, open: OpenEntry, end: DateTime<Local>, on_entry: &F)
+where
+    F: Fn(ActivityEntry),
+{
+    let dur = (end - open.start).num_seconds().max(0) as u64;
+    let category = Some(categories::categorize(data_dir, &open.exe, &open.title, open.domain.as_deref()));
+    let entry = ActivityEntry {
+        ts: open.start.format(TS_FORMAT).to_string(),
+        title: open.title,
+        exe: open.exe,
+        duration: dur,
+        afk: false,
+        category,
+        domain: open.domain,
+    };
+    record(data_dir, entry, on_entry);
+}
+
+/// 写入一条离开记录
+fn close_afk<F>(data_dir: &PathBuf, start: DateTime<Local>, end: DateTime<Local>, on_entry: &F)
+where
+    F: Fn(ActivityEntry),
+{
+    let dur = (end - start).num_seconds().max(0) as u64;
+    if dur == 0 {
+        return;
+    }
+    let entry = ActivityEntry {
+        ts: start.format(TS_FORMAT).to_string(),
+        title: AFK_TITLE.to_string(),
+        exe: String::new(),
+        duration: dur,
+        afk: true,
+        category: None,
+        domain: None,
+    };
+    record(data_dir, entry, on_entry);
+}
+
+// === 心跳检查点 ===
+
+/// 每次采样后写入 current.json，进程崩溃或断电后启动时据此补写进行中的记录
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    open: Option<OpenEntry>,
+    afk_since: Option<DateTime<Local>>,
+    heartbeat: DateTime<Local>,
+}
+
+fn checkpoint_path(data_dir: &PathBuf) -> PathBuf {
+    data_dir.join("current.json")
+}
+
+fn save_checkpoint(data_dir: &PathBuf, checkpoint: &Checkpoint) -> Result<(), String> {
+    fs::create_dir_all(data_dir).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(checkpoint).map_err(|e| e.to_string())?;
+    let tmp = data_dir.join("current.json.tmp");
+    fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, checkpoint_path(data_dir)).map_err(|e| e.to_string())
+}
+
+/// 恢复上次异常退出时未写盘的记录，时长算到最后一次心跳
+fn recover<F>(data_dir: &PathBuf, on_entry: &F)
+where
+    F: Fn(ActivityEntry),
+{
+    let path = checkpoint_path(data_dir);
+    let checkpoint = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<Checkpoint>(&s).ok());
+    if let Some(cp) = checkpoint {
+        if let Some(open) = cp.open {
+            close_entry(data_dir, open, cp.heartbeat, on_entry);
+        }
+        if let Some(since) = cp.afk_since {
+            close_afk(data_dir, since, cp.heartbeat, on_entry);
+        }
+    }
+    let _ = fs::remove_file(&path);
+}
+
+/// 追踪主循环：按 interval 采样，窗口变化或空闲时切分记录，退出时 flush
+///
+/// idle_threshold 秒内没有输入视为离开（0 表示只按锁屏判断），离开时段单独记为 afk 记录
+fn run_loop<S, C, F>(
+    source: &mut S,
+    clock: &C,
+    shared: &Mutex<Settings>,
+    data_dir: &PathBuf,
+    stop_flag: &AtomicBool,
+    on_entry: F,
+) where
+    S: WindowSource,
+    C: Clock,
+    F: Fn(ActivityEntry),
+{
+    recover(data_dir, &on_entry);
+
+    let mut open: Option<OpenEntry> = None;
+    let mut afk_since: Option<DateTime<Local>> = None;
+    let mut idle_threshold: Option<u64> = None;
+    let started = clock.now();
+
+    loop {
+        if stop_flag.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let settings = shared.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if idle_threshold != Some(settings.idle_threshold) {
+            source.set_idle_threshold(settings.idle_threshold);
+            idle_threshold = Some(settings.idle_threshold);
+        }
+        let now = clock.now();
+        let window = match source.sample(now) {
+            Some((title, exe)) => settings.privacy.redact(title, exe),
+            None => break,
+        };
+        let input_idle = if settings.idle_threshold > 0 {
+            source.idle_secs().filter(|secs| *secs >= settings.idle_threshold)
+        } else {
+            None
+        };
+        let screen_idle = window.as_ref().map(|(t, e)| is_idle(t, e)).unwrap_or(false);
+
+        if input_idle.is_some() || screen_idle {
+            // 无输入时回溯到最后一次输入，锁屏等只能从本次采样算起
+            let idle_start = match input_idle {
+                Some(secs) => (now - chrono::Duration::seconds(secs as i64)).max(started),
+                None => now,
+            };
+            if let Some(cur) = open.take() {
+                let end = idle_start.max(cur.start);
+                close_entry(data_dir, cur, end, &on_entry);
+                afk_since.get_or_insert(end);
+            } else {
+                afk_since.get_or_insert(idle_start);
+            }
+        } else if let Some((title, exe)) = window {
+            if let Some(since) = afk_since.take() {
+                close_afk(data_dir, since, now, &on_entry);
+            }
+            let domain = browser::domain_for(&title, &exe);
+            let same = open
+                .as_ref()
+                .map(|cur| cur.title == title && cur.exe == exe && cur.domain == domain)
+                .unwrap_or(false);
+            if !same {
+                if let Some(cur) = open.take() {
+                    close_entry(data_dir, cur, now, &on_entry);
+                }
+                open = Some(OpenEntry { start: now, title, exe, domain });
+            }
+        } else {
+            // 隐私规则要求丢弃的窗口：结束当前记录，这段时间既不算活动也不算离开
+            if let Some(since) = afk_since.take() {
+                close_afk(data_dir, since, now, &on_entry);
+            }
+            if let Some(cur) = open.take() {
+                close_entry(data_dir, cur, now, &on_entry);
+            }
+        }
+
+        let checkpoint = Checkpoint { open: open.clone(), afk_since, heartbeat: now };
+        let _ = save_checkpoint(data_dir, &checkpoint);
+
+        clock.sleep(settings.interval, stop_flag);
+    }
+
+    // 退出时 flush
+    let now = clock.now();
+    if let Some(cur) = open {
+        close_entry(data_dir, cur, now, &on_entry);
+    }
+    if let Some(since) = afk_since {
+        close_afk(data_dir, since, now, &on_entry);
+    }
+    let _ = fs::remove_file(checkpoint_path(data_dir));
+}
+
 impl Tracker {
-    pub fn new(interval: u64, data_dir: PathBuf) -> Self {
+    /// 使用运行时检测到的前台窗口后端（Win32 / X11 / Wayland）和系统时钟
+    pub fn new(interval: u64, idle_threshold: u64, data_dir: PathBuf) -> Self {
+        let backend = active_window::Backend::detect(idle_threshold);
+        Tracker::with_source(backend, SystemClock, interval, data_dir).idle_threshold(idle_threshold)
+    }
+}
+
+impl<S: WindowSource, C: Clock> Tracker<S, C> {
+    pub fn with_source(source: S, clock: C, interval: u64, data_dir: PathBuf) -> Self {
         Self {
-            interval,
+            settings: Arc::new(Mutex::new(Settings { interval, idle_threshold: 0, privacy: Privacy::default() })),
             data_dir,
+            source: Arc::new(Mutex::new(source)),
+            clock: Arc::new(clock),
             stop_flag: Arc::new(AtomicBool::new(false)),
             running: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// 设置输入空闲阈值（秒），0 表示不检测输入空闲
+    pub fn idle_threshold(self, secs: u64) -> Self {
+        self.settings.lock().unwrap_or_else(|e| e.into_inner()).idle_threshold = secs;
+        self
+    }
+
+    /// 设置隐私排除规则，命中的窗口按规则丢弃或脱敏后再记录
+    pub fn privacy(self, privacy: Privacy) -> Self {
+        self.settings.lock().unwrap_or_else(|e| e.into_inner()).privacy = privacy;
+        self
+    }
+
+    /// 更换数据目录，只在追踪停止时生效（迁移数据目录后调用）
+    pub fn set_data_dir(&mut self, data_dir: PathBuf) {
+        if !self.is_running() {
+            self.data_dir = data_dir;
+        }
+    }
+
+    /// 更新采样间隔、空闲阈值和隐私规则，正在运行的追踪循环从下一次采样起生效
+    pub fn reconfigure(&self, interval: u64, idle_threshold: u64, privacy: Privacy) {
+        let mut settings = self.settings.lock().unwrap_or_else(|e| e.into_inner());
+        settings.interval = interval;
+        settings.idle_threshold = idle_threshold;
+        settings.privacy = privacy;
+    }
+
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::Relaxed)
     }
@@ -135,83 +477,255 @@ impl Tracker {
         self.stop_flag.store(false, Ordering::Relaxed);
         self.running.store(true, Ordering::Relaxed);
 
-        let interval = self.interval;
+        let settings = self.settings.clone();
         let data_dir = self.data_dir.clone();
+        let source = self.source.clone();
+        let clock = self.clock.clone();
         let stop_flag = self.stop_flag.clone();
         let running = self.running.clone();
 
         std::thread::spawn(move || {
-            let mut last_title = String::new();
-            let mut last_exe = String::new();
-            let mut last_ts: Option<chrono::DateTime<Local>> = None;
+            if let Ok(mut source) = source.lock() {
+                run_loop(&mut *source, &*clock, &*settings, &data_dir, &stop_flag, on_entry);
+            }
+            running.store(false, Ordering::Relaxed);
+        });
+    }
 
-            loop {
-                if stop_flag.load(Ordering::Relaxed) {
-                    break;
-                }
+    /// 在当前线程同步运行，直到来源耗尽或被 stop（无界面回放用）
+    pub fn run_blocking<F>(&mut self, on_entry: F)
+    where
+        F: Fn(ActivityEntry),
+    {
+        if self.is_running() {
+            return;
+        }
+        self.stop_flag.store(false, Ordering::Relaxed);
+        self.running.store(true, Ordering::Relaxed);
+        if let Ok(mut source) = self.source.lock() {
+            run_loop(
+                &mut *source,
+                &*self.clock,
+                &self.settings,
+                &self.data_dir,
+                &self.stop_flag,
+                on_entry,
+            );
+        }
+        self.running.store(false, Ordering::Relaxed);
+    }
 
-                let (title, exe) = get_active_window();
-
-                if is_idle(&title, &exe) {
-                    if let Some(ts) = last_ts.take() {
-                        let dur = (Local::now() - ts).num_seconds().max(0) as u64;
-                        let entry = ActivityEntry {
-                            ts: ts.format("%Y-%m-%dT%H:%M:%S").to_string(),
-                            title: last_title.clone(),
-                            exe: last_exe.clone(),
-                            duration: dur,
-                        };
-                        let _ = write_entry(&data_dir, &entry);
-                        on_entry(entry);
-                        last_title.clear();
-                        last_exe.clear();
-                    }
-                } else if title == last_title && exe == last_exe {
-                    // 去重
-                } else {
-                    if let Some(ts) = last_ts.take() {
-                        let dur = (Local::now() - ts).num_seconds().max(0) as u64;
-                        let entry = ActivityEntry {
-                            ts: ts.format("%Y-%m-%dT%H:%M:%S").to_string(),
-                            title: last_title.clone(),
-                            exe: last_exe.clone(),
-                            duration: dur,
-                        };
-                        let _ = write_entry(&data_dir, &entry);
-                        on_entry(entry);
-                    }
-                    last_title = title;
-                    last_exe = exe;
-                    last_ts = Some(Local::now());
-                }
+    pub fn stop(&mut self) {
+        self.stop_flag.store(true, Ordering::Relaxed);
+    }
+}
+
+#[derive(Deserialize)]
+struct ReplayScript {
+    /// 回放起始时间，缺省为当前时间
+    #[serde(default)]
+    start: Option<String>,
+    samples: Vec<(String, String, u64)>,
+}
+
+/// 读取回放脚本，用 ScriptedSource + ManualClock 无界面跑一遍追踪器，返回写出的记录
+pub fn replay_file(path: &PathBuf, interval: u64, data_dir: PathBuf) -> Result<Vec<ActivityEntry>, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let script: ReplayScript = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    let start = match script.start {
+        Some(s) => NaiveDateTime::parse_from_str(&s, TS_FORMAT)
+            .map_err(|e| format!("start 格式应为 YYYY-MM-DDTHH:MM:SS: {}", e))?
+            .and_local_timezone(Local)
+            .single()
+            .ok_or_else(|| "start 时间无效".to_string())?,
+        None => Local::now(),
+    };
+
+    let entries = Arc::new(Mutex::new(Vec::new()));
+    let collected = entries.clone();
+    let mut tracker = Tracker::with_source(
+        ScriptedSource::new(script.samples),
+        ManualClock::new(start),
+        interval.max(1),
+        data_dir,
+    )
+    .privacy(Privacy::load());
+    tracker.run_blocking(move |entry| collected.lock().unwrap().push(entry));
+
+    let result = entries.lock().unwrap().clone();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xiaolanniao-tracker-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
 
-                // 分段 sleep，每秒检查一次 stop_flag
-                for _ in 0..interval {
-                    if stop_flag.load(Ordering::Relaxed) {
-                        break;
-                    }
-                    std::thread::sleep(Duration::from_secs(1));
+    fn at(ts: &str) -> DateTime<Local> {
+        NaiveDateTime::parse_from_str(ts, TS_FORMAT).unwrap().and_local_timezone(Local).single().unwrap()
+    }
+
+    fn sample(title: &str, exe: &str, secs: u64) -> (String, String, u64) {
+        (title.to_string(), exe.to_string(), secs)
+    }
+
+    /// 按脚本跑一遍追踪器，返回回调收到的记录
+    fn replay(data_dir: &PathBuf, start: &str, interval: u64, samples: Vec<(String, String, u64)>) -> Vec<ActivityEntry> {
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let collected = entries.clone();
+        let mut tracker =
+            Tracker::with_source(ScriptedSource::new(samples), ManualClock::new(at(start)), interval, data_dir.clone());
+        tracker.run_blocking(move |entry| collected.lock().unwrap().push(entry));
+        let result = entries.lock().unwrap().clone();
+        result
+    }
+
+    /// 脚本来源加上每次采样时的输入空闲秒数，记录收到的阈值，可在第 n 次采样前修改阈值
+    struct IdleSource {
+        inner: ScriptedSource,
+        idle: Vec<u64>,
+        calls: usize,
+        thresholds: Arc<Mutex<Vec<u64>>>,
+        change_at: Option<(usize, Arc<Mutex<Settings>>, u64)>,
+    }
+
+    impl WindowSource for IdleSource {
+        fn sample(&mut self, now: DateTime<Local>) -> Option<(String, String)> {
+            if let Some((n, settings, secs)) = &self.change_at {
+                if *n == self.calls {
+                    settings.lock().unwrap().idle_threshold = *secs;
                 }
             }
+            self.calls += 1;
+            self.inner.sample(now)
+        }
 
-            // 退出时 flush
-            if let Some(ts) = last_ts {
-                let dur = (Local::now() - ts).num_seconds().max(0) as u64;
-                let entry = ActivityEntry {
-                    ts: ts.format("%Y-%m-%dT%H:%M:%S").to_string(),
-                    title: last_title,
-                    exe: last_exe,
-                    duration: dur,
-                };
-                let _ = write_entry(&data_dir, &entry);
-                on_entry(entry);
-            }
+        fn idle_secs(&mut self) -> Option<u64> {
+            self.idle.get(self.calls - 1).copied()
+        }
 
-            running.store(false, Ordering::Relaxed);
-        });
+        fn set_idle_threshold(&mut self, secs: u64) {
+            self.thresholds.lock().unwrap().push(secs);
+        }
     }
 
-    pub fn stop(&mut self) {
-        self.stop_flag.store(true, Ordering::Relaxed);
+    /// 用 IdleSource 跑一遍追踪器，返回记录和来源收到的阈值
+    fn replay_idle(
+        data_dir: &PathBuf,
+        threshold: u64,
+        idle: Vec<u64>,
+        change_at: Option<(usize, u64)>,
+    ) -> (Vec<(bool, u64)>, Vec<u64>) {
+        let thresholds = Arc::new(Mutex::new(Vec::new()));
+        let source = IdleSource {
+            inner: ScriptedSource::new(vec![sample("main.rs", "code.exe", 60)]),
+            idle,
+            calls: 0,
+            thresholds: thresholds.clone(),
+            change_at: None,
+        };
+        let mut tracker = Tracker::with_source(source, ManualClock::new(at("2026-03-01T09:00:00")), 10, data_dir.clone())
+            .idle_threshold(threshold);
+        tracker.source.lock().unwrap().change_at = change_at.map(|(n, secs)| (n, tracker.settings.clone(), secs));
+        let entries = Arc::new(Mutex::new(Vec::new()));
+        let collected = entries.clone();
+        tracker.run_blocking(move |e| collected.lock().unwrap().push((e.afk, e.duration)));
+        let entries = entries.lock().unwrap().clone();
+        let thresholds = thresholds.lock().unwrap().clone();
+        (entries, thresholds)
+    }
+
+    fn read_day(data_dir: &PathBuf, date: &str) -> Vec<ActivityEntry> {
+        fs::read_to_string(activity_dir(data_dir).join(format!("{}.jsonl", date)))
+            .unwrap_or_default()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn window_change_splits_entries() {
+        let dir = temp_dir("split");
+        let entries = replay(
+            &dir,
+            "2026-03-01T09:00:00",
+            10,
+            vec![sample("main.rs", "code.exe", 30), sample("GitHub", "chrome.exe", 20)],
+        );
+        let got: Vec<_> = entries.iter().map(|e| (e.ts.as_str(), e.exe.as_str(), e.duration)).collect();
+        assert_eq!(got, [("2026-03-01T09:00:00", "code.exe", 30), ("2026-03-01T09:00:30", "chrome.exe", 20)]);
+        assert_eq!(read_day(&dir, "2026-03-01").len(), 2);
+        assert!(!checkpoint_path(&dir).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn entry_across_midnight_is_split_per_day() {
+        let dir = temp_dir("midnight");
+        let entries = replay(&dir, "2026-03-01T23:59:30", 10, vec![sample("main.rs", "code.exe", 60)]);
+        let got: Vec<_> = entries.iter().map(|e| (e.ts.as_str(), e.duration)).collect();
+        assert_eq!(got, [("2026-03-01T23:59:30", 30), ("2026-03-02T00:00:00", 30)]);
+        assert_eq!(read_day(&dir, "2026-03-01").len(), 1);
+        assert_eq!(read_day(&dir, "2026-03-02").len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn lock_screen_becomes_afk_entry() {
+        let dir = temp_dir("lock");
+        let entries = replay(
+            &dir,
+            "2026-03-01T09:00:00",
+            10,
+            vec![sample("main.rs", "code.exe", 20), sample("", "LockApp.exe", 30), sample("main.rs", "code.exe", 10)],
+        );
+        let got: Vec<_> = entries.iter().map(|e| (e.afk, e.duration)).collect();
+        assert_eq!(got, [(false, 20), (true, 30), (false, 10)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn input_idle_backdates_afk_to_last_input() {
+        let dir = temp_dir("idle");
+        // 09:00:30 时已 12 秒无输入，离开从 09:00:18 算起，09:00:50 恢复输入
+        let (entries, _) = replay_idle(&dir, 10, vec![0, 0, 0, 12, 22, 0], None);
+        assert_eq!(entries, [(false, 18), (true, 32), (false, 10)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn idle_threshold_change_applies_while_running() {
+        let dir = temp_dir("threshold");
+        // 阈值为 0 时不看输入空闲；第 3 次采样前改成 10 秒，来源收到新阈值，之后的空闲切成 afk
+        let (entries, thresholds) = replay_idle(&dir, 0, vec![0, 30, 30, 0, 15, 25], Some((2, 10)));
+        assert_eq!(thresholds, [0, 10]);
+        assert_eq!(entries, [(false, 25), (true, 35)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn write_entry_appends_to_day_file() {
+        let dir = temp_dir("write");
+        let entry = |ts: &str| ActivityEntry {
+            ts: ts.to_string(),
+            title: "main.rs".to_string(),
+            exe: "code.exe".to_string(),
+            duration: 5,
+            afk: false,
+            category: None,
+            domain: None,
+        };
+        write_entry(&dir, &entry("2026-03-01T09:00:00")).unwrap();
+        write_entry(&dir, &entry("2026-03-01T09:00:05")).unwrap();
+        write_entry(&dir, &entry("2026-03-02T09:00:00")).unwrap();
+        let day: Vec<_> = read_day(&dir, "2026-03-01").into_iter().map(|e| e.ts).collect();
+        assert_eq!(day, ["2026-03-01T09:00:00", "2026-03-01T09:00:05"]);
+        assert_eq!(read_day(&dir, "2026-03-02").len(), 1);
+        let _ = fs::remove_dir_all(&dir);
     }
 }
@@ -0,0 +1,320 @@
+use crate::activity;
+use crate::categories;
+use crate::tracker::{self, ActivityEntry};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+const TS_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// 导出时的一行：原始记录 + 计算出的分类
+#[derive(Debug, Serialize)]
+struct ExportRow<'a> {
+    ts: &'a str,
+    title: &'a str,
+    exe: &'a str,
+    duration: u64,
+    afk: bool,
+    category: String,
+    domain: &'a str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub imported: u32,
+    /// 已存在（同一时间、进程、标题）而跳过的条数
+    pub skipped: u32,
+    pub days: u32,
+}
+
+/// 导出 [from, to] 的活动记录到 path，format 为 csv 或 json，返回导出条数
+pub fn export(data_dir: &PathBuf, from: NaiveDate, to: NaiveDate, format: &str, path: &PathBuf) -> Result<u32, String> {
+    let rules = categories::load(data_dir);
+    let mut entries = Vec::new();
+    for date in from.iter_days().take_while(|d| *d <= to) {
+        entries.extend(activity::load_entries(data_dir, &date.format("%Y-%m-%d").to_string()));
+    }
+    let rows: Vec<ExportRow> = entries
+        .iter()
+        .map(|e| ExportRow {
+            ts: &e.ts,
+            title: &e.title,
+            exe: &e.exe,
+            duration: e.duration,
+            afk: e.afk,
+            category: if e.afk { String::new() } else { activity::category_of(&rules, e) },
+            domain: e.domain.as_deref().unwrap_or(""),
+        })
+        .collect();
+
+    match format {
+        "json" => {
+            let json = serde_json::to_string_pretty(&rows).map_err(|e| e.to_string())?;
+            fs::write(path, json).map_err(|e| e.to_string())?;
+        }
+        "csv" => {
+            let mut w = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
+            for row in &rows {
+                w.serialize(row).map_err(|e| e.to_string())?;
+            }
+            w.flush().map_err(|e| e.to_string())?;
+        }
+        other => return Err(format!("不支持的导出格式: {}", other)),
+    }
+    Ok(rows.len() as u32)
+}
+
+// === 导入 ===
+
+/// 导入 ActivityWatch 导出的 JSON 或 RescueTime 的 CSV，source 为空时按扩展名判断
+pub fn import(data_dir: &PathBuf, path: &PathBuf, source: Option<&str>) -> Result<ImportReport, String> {
+    let source = match source {
+        Some(s) => s.to_string(),
+        None => match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+            Some("json") => "activitywatch".to_string(),
+            Some("csv") => "rescuetime".to_string(),
+            _ => return Err("无法识别的文件类型，请指定来源".into()),
+        },
+    };
+    let entries = match source.as_str() {
+        "activitywatch" => parse_activitywatch(&fs::read_to_string(path).map_err(|e| e.to_string())?)?,
+        "rescuetime" => parse_rescuetime(path)?,
+        other => return Err(format!("不支持的导入来源: {}", other)),
+    };
+    write_imported(data_dir, entries)
+}
+
+/// 按天合并进已有记录：跨零点的记录和监测时一样拆到各自日期，跳过重复，按时间排序后整体重写
+fn write_imported(data_dir: &PathBuf, entries: Vec<ActivityEntry>) -> Result<ImportReport, String> {
+    let rules = categories::load(data_dir);
+    let mut by_day: BTreeMap<String, Vec<ActivityEntry>> = BTreeMap::new();
+    for mut e in entries {
+        if e.duration == 0 {
+            continue;
+        }
+        if !e.afk {
+            e.category = Some(rules.categorize_entry(&e));
+        }
+        for part in tracker::split_at_midnight(e) {
+            by_day.entry(part.ts[..10].to_string()).or_default().push(part);
+        }
+    }
+
+    let mut report = ImportReport { imported: 0, skipped: 0, days: 0 };
+    for (date, new_entries) in by_day {
+        activity::update_entries(data_dir, &date, |existing| {
+            let mut seen: HashSet<(String, String, String)> = existing
+                .iter()
+                .map(|e| (e.ts.clone(), e.exe.clone(), e.title.clone()))
+                .collect();
+            let before = existing.len();
+            for e in new_entries {
+                if seen.insert((e.ts.clone(), e.exe.clone(), e.title.clone())) {
+                    existing.push(e);
+                } else {
+                    report.skipped += 1;
+                }
+            }
+            let added = existing.len() - before;
+            if added == 0 {
+                return false;
+            }
+            report.imported += added as u32;
+            report.days += 1;
+            existing.sort_by(|a, b| a.ts.cmp(&b.ts));
+            true
+        })?;
+    }
+    Ok(report)
+}
+
+fn local_ts(t: DateTime<Local>) -> String {
+    t.format(TS_FORMAT).to_string()
+}
+
+// --- ActivityWatch ---
+
+#[derive(Deserialize)]
+struct AwEvent {
+    timestamp: DateTime<chrono::FixedOffset>,
+    #[serde(default)]
+    duration: f64,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct AwBucket {
+    #[serde(default)]
+    id: String,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    events: Vec<AwEvent>,
+}
+
+/// 解析 ActivityWatch 导出：支持整库导出 {"buckets": {...}} 和单个 bucket
+///
+/// 窗口 bucket 转为活动记录，afk bucket 中 status 为 afk 的时段转为离开记录，并从窗口记录中扣除
+fn parse_activitywatch(content: &str) -> Result<Vec<ActivityEntry>, String> {
+    let value: serde_json::Value = serde_json::from_str(content).map_err(|e| format!("JSON 解析失败: {}", e))?;
+    let buckets: Vec<AwBucket> = match value.get("buckets") {
+        Some(serde_json::Value::Object(map)) => map
+            .values()
+            .filter_map(|b| serde_json::from_value(b.clone()).ok())
+            .collect(),
+        _ => vec![serde_json::from_value(value).map_err(|e| format!("不是 ActivityWatch 导出文件: {}", e))?],
+    };
+
+    let mut windows: Vec<(DateTime<Local>, DateTime<Local>, String, String)> = Vec::new();
+    let mut afk: Vec<(DateTime<Local>, DateTime<Local>)> = Vec::new();
+    for bucket in &buckets {
+        let is_afk = bucket.kind == "afkstatus" || bucket.id.starts_with("aw-watcher-afk");
+        let is_window = bucket.kind == "currentwindow" || bucket.id.starts_with("aw-watcher-window");
+        for ev in &bucket.events {
+            let start = ev.timestamp.with_timezone(&Local);
+            let end = start + chrono::Duration::milliseconds((ev.duration * 1000.0) as i64);
+            if is_afk {
+                if ev.data.get("status").and_then(|s| s.as_str()) == Some("afk") {
+                    afk.push((start, end));
+                }
+            } else if is_window {
+                let text = |k: &str| ev.data.get(k).and_then(|v| v.as_str()).unwrap_or("").to_string();
+                windows.push((start, end, text("title"), text("app")));
+            }
+        }
+    }
+    if windows.is_empty() && afk.is_empty() {
+        return Err("文件中没有窗口或 afk 事件".into());
+    }
+    afk.sort();
+
+    let mut entries = Vec::new();
+    for (start, end, title, exe) in windows {
+        for (s, e) in subtract(start, end, &afk) {
+            entries.push(ActivityEntry {
+                ts: local_ts(s),
+                title: title.clone(),
+                exe: exe.clone(),
+                duration: (e - s).num_seconds().max(0) as u64,
+                afk: false,
+                category: None,
+                domain: None,
+            });
+        }
+    }
+    for (s, e) in afk {
+        entries.push(ActivityEntry {
+            ts: local_ts(s),
+            title: crate::tracker::AFK_TITLE.to_string(),
+            exe: String::new(),
+            duration: (e - s).num_seconds().max(0) as u64,
+            afk: true,
+            category: None,
+            domain: None,
+        });
+    }
+    Ok(entries)
+}
+
+/// 从 [start, end) 中扣除已排序的离开时段，返回剩余的片段
+fn subtract(
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    holes: &[(DateTime<Local>, DateTime<Local>)],
+) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+    let mut parts = Vec::new();
+    let mut cur = start;
+    for (hs, he) in holes {
+        if *he <= cur || *hs >= end {
+            continue;
+        }
+        if *hs > cur {
+            parts.push((cur, *hs));
+        }
+        cur = cur.max(*he);
+        if cur >= end {
+            break;
+        }
+    }
+    if cur < end {
+        parts.push((cur, end));
+    }
+    parts
+}
+
+// --- RescueTime ---
+
+/// 解析 RescueTime CSV，按表头识别列：
+/// 开始时间（Date / Start Time / timestamp）、时长（Time Spent (seconds) / Duration，或由 End Time 推算）、
+/// 活动（Activity / Application）和细节（Document / Details，作为标题）
+fn parse_rescuetime(path: &PathBuf) -> Result<Vec<ActivityEntry>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| e.to_string())?;
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let col = |names: &[&str]| headers.iter().position(|h| names.contains(&h.as_str()));
+
+    let start_col = col(&["date", "start time", "start_time", "timestamp"]).ok_or("CSV 缺少开始时间列")?;
+    let dur_col = col(&["time spent (seconds)", "duration", "seconds", "duration (seconds)"]);
+    let end_col = col(&["end time", "end_time"]);
+    let app_col = col(&["activity", "application", "app"]).ok_or("CSV 缺少 Activity 列")?;
+    let detail_col = col(&["document", "details", "title"]);
+    if dur_col.is_none() && end_col.is_none() {
+        return Err("CSV 缺少时长或结束时间列".into());
+    }
+
+    let mut entries = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let field = |i: usize| record.get(i).unwrap_or("").trim();
+        let start = match parse_time(field(start_col)) {
+            Some(t) => t,
+            None => continue,
+        };
+        let duration = match (dur_col, end_col) {
+            (Some(i), _) => field(i).parse::<f64>().map(|d| d as u64).unwrap_or(0),
+            (None, Some(i)) => parse_time(field(i)).map(|e| (e - start).num_seconds().max(0) as u64).unwrap_or(0),
+            (None, None) => 0,
+        };
+        let app = field(app_col).to_string();
+        // RescueTime 的网站活动直接以域名作为 Activity
+        let domain = if app.contains('.') && !app.contains(' ') && !app.to_lowercase().ends_with(".exe") {
+            Some(app.trim_start_matches("www.").to_lowercase())
+        } else {
+            None
+        };
+        let detail = detail_col.map(field).filter(|d| !d.is_empty() && *d != "No Details");
+        entries.push(ActivityEntry {
+            ts: local_ts(start),
+            title: detail.map(|d| d.to_string()).unwrap_or_else(|| app.clone()),
+            exe: app,
+            duration,
+            afk: false,
+            category: None,
+            domain,
+        });
+    }
+    Ok(entries)
+}
+
+/// 解析常见时间格式：带时区的 RFC 3339，或按本地时间解释的无时区格式
+fn parse_time(s: &str) -> Option<DateTime<Local>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t.with_timezone(&Local));
+    }
+    for fmt in [TS_FORMAT, "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%m/%d/%Y %H:%M:%S", "%m/%d/%Y %H:%M"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(s, fmt) {
+            return Local.from_local_datetime(&t).earliest();
+        }
+    }
+    None
+}
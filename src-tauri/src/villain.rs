@@ -1,4 +1,4 @@
-use crate::activity;
+use crate::rollup;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
@@ -44,12 +44,7 @@ pub fn villain_growth(data_dir: &PathBuf, category: &str) -> u32 {
     for i in 0..7 {
         let date = today - chrono::Duration::days(i as i64);
         let ds = date.format("%Y-%m-%d").to_string();
-        let entries = activity::load_entries(data_dir, &ds);
-        let summary = activity::summarize(&entries);
-        let cat_min = summary.iter()
-            .find(|(c, _)| c == category)
-            .map(|(_, s)| s / 60)
-            .unwrap_or(0);
+        let cat_min = rollup::day(data_dir, &ds).category_sec(category) / 60;
         if cat_min >= 30 { streak += 1; } else { break; }
     }
     streak
@@ -57,8 +52,7 @@ pub fn villain_growth(data_dir: &PathBuf, category: &str) -> u32 {
 
 pub fn detect(data_dir: &PathBuf) -> Option<Villain> {
     let today = chrono::Local::now().format("%Y-%m-%d").to_string();
-    let entries = activity::load_entries(data_dir, &today);
-    let summary = activity::summarize(&entries);
+    let summary = rollup::day(data_dir, &today).by_category;
 
     // 加载今日封印记录
     let seals = load_seals(data_dir, &today);
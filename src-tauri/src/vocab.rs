@@ -26,7 +26,8 @@ pub struct VocabExample {
 
 /// 从窗口标题中提取英文单词（4字母以上，排除常见词）
 pub fn extract_words(data_dir: &PathBuf, date: &str) -> Vec<(String, String)> {
-    let entries = activity::load_entries(data_dir, date);
+    // 单词会发给 AI 造句，先按隐私规则脱敏
+    let entries = crate::privacy::Privacy::load().redact_entries(activity::load_entries(data_dir, date));
     let stop_words: HashSet<&str> = [
         "the","and","for","are","but","not","you","all","can","had","her",
         "was","one","our","out","with","that","this","from","have","been",
@@ -74,7 +74,7 @@ pub fn settle_day(data_dir: &PathBuf, date: &str) -> WorldState {
     }
 
     let entries = activity::load_entries(data_dir, date);
-    let summary = activity::summarize(&entries);
+    let summary = activity::summarize(data_dir, &entries);
     let mut changes: HashMap<String, u32> = HashMap::new();
 
     // 活动类别转换为区域 XP
@@ -124,7 +124,7 @@ pub fn settle_day(data_dir: &PathBuf, date: &str) -> WorldState {
 fn count_late_night_minutes(entries: &[crate::tracker::ActivityEntry]) -> u32 {
     let mut total = 0u32;
     for e in entries {
-        if e.ts.len() >= 16 {
+        if !e.afk && e.ts.len() >= 16 {
             let hour: u32 = e.ts[11..13].parse().unwrap_or(12);
             if hour >= 23 || hour < 5 {
                 total += (e.duration / 60) as u32;
@@ -35,6 +35,24 @@ updateStatusBarDate();
 const { invoke } = window.__TAURI__.core;
 const { listen } = window.__TAURI__.event;
 
+// === AI 请求 ===
+// 每次 AI 调用带上自己的 request_id，事件按 id 过滤，多个请求同时进行时不会串流
+function newAiRequestId() {
+  return `ai-${Date.now()}-${Math.random().toString(36).slice(2, 8)}`;
+}
+
+// 只接收某次请求的事件；ai-chunk 回调收到文本，其余事件收到完整负载
+function listenAi(requestId, eventName, handler) {
+  return listen(eventName, (e) => {
+    if (!e.payload || e.payload.id !== requestId) return;
+    handler(eventName === 'ai-chunk' ? e.payload.text : e.payload);
+  });
+}
+
+function cancelAiRequest(requestId) {
+  return invoke('cancel_ai_request', { id: requestId });
+}
+
 // === 左侧导航栏折叠 ===
 const navRail = document.getElementById('navRail');
 const navLogo = navRail.querySelector('.nav-logo');
@@ -261,12 +261,11 @@
     resultText.textContent = '正在解读你的梦境…';
 
     let fullText = '';
-    const chunkHandler = (e) => {
-      fullText += e.payload;
+    const requestId = newAiRequestId();
+    const unlisten = await listenAi(requestId, 'ai-chunk', (text) => {
+      fullText += text;
       resultText.textContent = fullText;
-    };
-    const { listen } = window.__TAURI__.event;
-    const unlisten = await listen('ai-chunk', chunkHandler);
+    });
 
     try {
       const result = await invoke('ai_dream_analysis', {
@@ -274,6 +273,7 @@
         content,
         mood: selectedMood || '奇妙',
         lucid: document.getElementById('dreamLucid').checked,
+        request_id: requestId,
       });
 
       // 最终结果覆盖（防止 chunk 丢失）
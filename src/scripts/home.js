@@ -304,17 +304,18 @@ theaterBtn?.addEventListener('click', async () => {
 
   // 流式生成
   let fullText = '';
-  const unlisten = await listen('ai-chunk', (e) => {
-    fullText += e.payload;
+  const requestId = newAiRequestId();
+  const unlisten = await listenAi(requestId, 'ai-chunk', (text) => {
+    fullText += text;
     theaterContent.textContent = fullText;
   });
-  const unlistenDone = await listen('ai-done', () => {
+  const unlistenDone = await listenAi(requestId, 'ai-done', () => {
     unlisten(); unlistenDone();
     parseAndRenderTheater(today, fullText);
   });
 
   try {
-    await invoke('generate_theater', { date: today });
+    await invoke('generate_theater', { date: today, request_id: requestId });
   } catch(err) {
     theaterContent.innerHTML = `<div class="empty-hint">生成失败: ${err}</div>`;
     unlisten(); unlistenDone();
@@ -394,10 +395,11 @@ vocabGenBtn?.addEventListener('click', async () => {
 
   const today = new Date().toISOString().slice(0, 10);
   let fullText = '';
-  const unlisten = await listen('ai-chunk', (e) => {
-    fullText += e.payload;
+  const requestId = newAiRequestId();
+  const unlisten = await listenAi(requestId, 'ai-chunk', (text) => {
+    fullText += text;
   });
-  const unlistenDone = await listen('ai-done', () => {
+  const unlistenDone = await listenAi(requestId, 'ai-done', () => {
     unlisten(); unlistenDone();
     try {
       // 解析 JSON
@@ -411,7 +413,7 @@ vocabGenBtn?.addEventListener('click', async () => {
   });
 
   try {
-    await invoke('generate_vocab_examples', { date: today, words: todayWords });
+    await invoke('generate_vocab_examples', { date: today, words: todayWords, request_id: requestId });
   } catch(err) {
     vocabList.innerHTML = `<div class="empty-hint">生成失败: ${err}</div>`;
     unlisten(); unlistenDone();
@@ -115,11 +115,12 @@
     textEl.textContent = '';
 
     let fullText = '';
-    const unlisten = await listen('ai-chunk', (e) => {
-      fullText += e.payload;
+    const requestId = newAiRequestId();
+    const unlisten = await listenAi(requestId, 'ai-chunk', (text) => {
+      fullText += text;
       textEl.textContent = fullText;
     });
-    const unlistenDone = await listen('ai-done', () => {
+    const unlistenDone = await listenAi(requestId, 'ai-done', () => {
       unlisten(); unlistenDone();
       // 解析标题（第一行）
       const lines = fullText.split('\n');
@@ -132,7 +133,7 @@
     });
 
     try {
-      await invoke('alchemy_synthesize', { note_ids: ids });
+      await invoke('alchemy_synthesize', { note_ids: ids, request_id: requestId });
     } catch(err) {
       titleEl.textContent = '炼金失败';
       textEl.textContent = err;
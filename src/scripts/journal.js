@@ -306,13 +306,14 @@ importBtn.addEventListener('click', async () => {
     journalStatus.textContent = P().journal.merging;
     journalEditor.value = '';
 
-    const unlisten = await listen('ai-chunk', (event) => {
-      journalEditor.value += event.payload;
+    const requestId = newAiRequestId();
+    const unlisten = await listenAi(requestId, 'ai-chunk', (text) => {
+      journalEditor.value += text;
       journalEditor.scrollTop = journalEditor.scrollHeight;
     });
 
     try {
-      await invoke('ai_organize', { date: journalDate, raw_content: merged });
+      await invoke('ai_organize', { date: journalDate, raw_content: merged, request_id: requestId });
       journalStatus.textContent = P().journal.merge_done;
     } catch (e) {
       journalStatus.textContent = `整合失败：${String(e).slice(0, 50)}`;
@@ -327,30 +328,50 @@ importBtn.addEventListener('click', async () => {
   // cancel: do nothing
 });
 
+// 进行中的日记 AI 请求，按钮在生成期间变为「停止」
+let journalAiRequest = null;
+
 // AI 生成（流式）
 aiGenBtn.addEventListener('click', async () => {
-  aiGenBtn.disabled = true;
+  if (journalAiRequest) {
+    cancelAiRequest(journalAiRequest);
+    return;
+  }
+  const requestId = newAiRequestId();
+  journalAiRequest = requestId;
   organizeBtn.disabled = true;
-  aiGenBtn.textContent = '生成中...';
+  aiGenBtn.textContent = '停止';
   journalStatus.textContent = P().journal.generating;
 
   const existing = journalEditor.value.trim();
   const prefix = existing ? existing + '\n\n---\n\n' : '';
   journalEditor.value = prefix;
 
-  const unlisten = await listen('ai-chunk', (event) => {
-    journalEditor.value += event.payload;
+  const unlisten = await listenAi(requestId, 'ai-chunk', (text) => {
+    journalEditor.value += text;
     journalEditor.scrollTop = journalEditor.scrollHeight;
   });
+  // 流中途断开时保留已生成的部分，并提示不完整；写入用量或历史失败时附在结果后面
+  let incomplete = null;
+  let warning = null;
+  const unlistenDone = await listenAi(requestId, 'ai-done', (p) => {
+    if (p.incomplete) incomplete = p.error;
+    warning = p.warning;
+  });
+  const unlistenRetry = await listenAi(requestId, 'ai-retry', (p) => {
+    journalStatus.textContent = `${p.error.slice(0, 30)}，${p.wait_secs} 秒后第 ${p.attempt} 次重试…`;
+  });
 
   try {
-    await invoke('ai_generate', { date: journalDate, existing_text: existing });
-    journalStatus.textContent = P().journal.gen_done;
+    await invoke('ai_generate', { date: journalDate, existing_text: existing, request_id: requestId });
+    journalStatus.textContent = (incomplete ? `内容不完整：${incomplete.slice(0, 50)}` : P().journal.gen_done) + (warning ? `（${warning}）` : '');
   } catch (e) {
     journalStatus.textContent = `生成失败：${String(e).slice(0, 50)}`;
   } finally {
     unlisten();
-    aiGenBtn.disabled = false;
+    unlistenDone();
+    unlistenRetry();
+    journalAiRequest = null;
     organizeBtn.disabled = false;
     aiGenBtn.textContent = 'AI 生成';
   }
@@ -358,31 +379,49 @@ aiGenBtn.addEventListener('click', async () => {
 
 // AI 整理（流式）
 organizeBtn.addEventListener('click', async () => {
+  if (journalAiRequest) {
+    cancelAiRequest(journalAiRequest);
+    return;
+  }
   const content = journalEditor.value.trim();
   if (!content) {
     journalStatus.textContent = '没有内容可以整理';
     return;
   }
-  organizeBtn.disabled = true;
+  const requestId = newAiRequestId();
+  journalAiRequest = requestId;
   aiGenBtn.disabled = true;
-  organizeBtn.textContent = '整理中...';
+  organizeBtn.textContent = '停止';
   journalStatus.textContent = P().journal.organizing;
 
   journalEditor.value = '';
 
-  const unlisten = await listen('ai-chunk', (event) => {
-    journalEditor.value += event.payload;
+  const unlisten = await listenAi(requestId, 'ai-chunk', (text) => {
+    journalEditor.value += text;
     journalEditor.scrollTop = journalEditor.scrollHeight;
   });
+  // 流中途断开时保留已生成的部分，并提示不完整；写入用量或历史失败时附在结果后面
+  let incomplete = null;
+  let warning = null;
+  const unlistenDone = await listenAi(requestId, 'ai-done', (p) => {
+    if (p.incomplete) incomplete = p.error;
+    warning = p.warning;
+  });
+  const unlistenRetry = await listenAi(requestId, 'ai-retry', (p) => {
+    journalStatus.textContent = `${p.error.slice(0, 30)}，${p.wait_secs} 秒后第 ${p.attempt} 次重试…`;
+  });
 
   try {
-    await invoke('ai_organize', { date: journalDate, raw_content: content });
-    journalStatus.textContent = P().journal.org_done;
+    await invoke('ai_organize', { date: journalDate, raw_content: content, request_id: requestId });
+    journalStatus.textContent = (incomplete ? `内容不完整：${incomplete.slice(0, 50)}` : P().journal.org_done) + (warning ? `（${warning}）` : '');
   } catch (e) {
     journalStatus.textContent = `整理失败：${String(e).slice(0, 50)}`;
     journalEditor.value = content;
   } finally {
     unlisten();
+    unlistenDone();
+    unlistenRetry();
+    journalAiRequest = null;
     organizeBtn.disabled = false;
     aiGenBtn.disabled = false;
     organizeBtn.textContent = '整理';
@@ -39,7 +39,7 @@ function updateStatusBar() {
 
 function updateStats() {
   entryCountEl.textContent = entries.length;
-  const total = entries.reduce((s, e) => s + (e.duration || 0), 0);
+  const total = entries.filter(e => !e.afk).reduce((s, e) => s + (e.duration || 0), 0);
   totalDurationEl.textContent = fmtDuration(total);
 }
 
@@ -88,8 +88,9 @@ listen('new-activity-entry', (event) => {
   updateStatusBar();
 });
 
-// 初始化
+// 初始化（启动时可能已自动开始监测）
 loadTodayActivity();
+invoke('get_monitor_status').then(setMonitorUI).catch(e => console.error('monitor status:', e));
 
 // === 数据统计 ===
 const dailyChartEl = document.getElementById('dailyChart');
@@ -103,6 +104,13 @@ const CAT_COLORS = {
   '音乐': '#FF2D55', '其他': '#8E8E93',
 };
 
+// 用户在 categories.json 里自定义的分类颜色
+invoke('get_category_rules').then(rules => {
+  for (const c of rules.categories || []) {
+    if (c.color) CAT_COLORS[c.name] = c.color;
+  }
+}).catch(() => {});
+
 statsTabs.forEach(tab => {
   tab.addEventListener('click', () => {
     statsTabs.forEach(t => t.classList.remove('active'));
@@ -175,6 +183,22 @@ let reportText = '';
 
 weekReportBtn.addEventListener('click', () => generateReport(7));
 monthReportBtn.addEventListener('click', () => generateReport(30));
+document.getElementById('lastWeekReportBtn').addEventListener('click', () => {
+  // 上一个自然周（周一到周日）
+  const d = new Date();
+  const mon = new Date(d.getFullYear(), d.getMonth(), d.getDate() - ((d.getDay() + 6) % 7) - 7);
+  const sun = new Date(mon.getFullYear(), mon.getMonth(), mon.getDate() + 6);
+  generateReport(7, `${fmtDate(mon)}..${fmtDate(sun)}`);
+});
+document.getElementById('lastMonthReportBtn').addEventListener('click', () => {
+  const d = new Date();
+  const m = new Date(d.getFullYear(), d.getMonth() - 1, 1);
+  generateReport(30, `${m.getFullYear()}-${String(m.getMonth() + 1).padStart(2, '0')}`);
+});
+
+function fmtDate(d) {
+  return `${d.getFullYear()}-${String(d.getMonth() + 1).padStart(2, '0')}-${String(d.getDate()).padStart(2, '0')}`;
+}
 
 reportCloseBtn.addEventListener('click', () => {
   reportOverlay.classList.remove('open');
@@ -184,25 +208,37 @@ reportOverlay.addEventListener('click', (e) => {
   if (e.target === reportOverlay) reportOverlay.classList.remove('open');
 });
 
-async function generateReport(days) {
+async function generateReport(days, period) {
   reportTitle.textContent = days <= 7 ? 'AI 周报' : 'AI 月报';
   reportContent.innerHTML = '<div class="empty-hint">生成中…</div>';
   reportOverlay.classList.add('open');
   reportText = '';
 
   // 监听流式输出
-  const unlisten = await listen('ai-chunk', (event) => {
-    reportText += event.payload;
+  const requestId = newAiRequestId();
+  const unlisten = await listenAi(requestId, 'ai-chunk', (text) => {
+    reportText += text;
     reportContent.innerHTML = simpleMarkdownReport(reportText);
   });
+  // 流中途断开时在已生成的内容后提示
+  const unlistenDone = await listenAi(requestId, 'ai-done', (p) => {
+    if (p.incomplete) {
+      reportContent.innerHTML += `<div class="empty-hint">内容不完整：${p.error}</div>`;
+    }
+  });
 
   try {
-    await invoke('ai_report', { days });
+    if (period) {
+      await invoke('ai_report_period', { period, request_id: requestId });
+    } else {
+      await invoke('ai_report', { days, request_id: requestId });
+    }
   } catch (e) {
     reportContent.innerHTML = `<div class="empty-hint">生成失败: ${e}</div>`;
   }
 
   unlisten();
+  unlistenDone();
 }
 
 function simpleMarkdownReport(text) {
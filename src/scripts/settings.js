@@ -18,7 +18,6 @@ settingsOverlay.addEventListener('click', (e) => {
 });
 
 // === 设置表单 ===
-const apiKeyInput = document.getElementById('apiKeyInput');
 const langSelect = document.getElementById('langSelect');
 const dataDirInput = document.getElementById('dataDirInput');
 const browseDirBtn = document.getElementById('browseDirBtn');
@@ -57,8 +56,27 @@ if (birdSizeGrid) {
 
 // === AI 引擎管理 ===
 let aiProviders = [];
+// 上次加载的完整配置，保存时保留界面上没有的字段
+let loadedConfig = {};
 let activeProviderId = '';
-let writingProviderId = '';
+// 按功能指定的引擎 id，空表示跟随默认
+let featureProviders = {};
+const AI_FEATURES = [
+  ['diary', '日记'], ['theater', '小剧场'], ['vocab', '单词'],
+  ['alchemy', '灵感炼金'], ['writing', '写作'], ['dream', '梦境'],
+];
+// 接口协议，占位符提示各协议的默认地址和模型写法
+const PROVIDER_PROTOCOLS = {
+  openai: { label: 'OpenAI 兼容', key: 'sk-...', url: 'https://api.deepseek.com/v1', model: 'deepseek-chat' },
+  anthropic: { label: 'Anthropic', key: 'sk-ant-...', url: 'https://api.anthropic.com/v1', model: 'claude-sonnet-4-5' },
+  ollama: { label: 'Ollama（本地）', key: '无需填写', url: 'http://localhost:11434', model: 'qwen2.5:7b' },
+  mock: { label: '模拟（离线测试）', key: '无需填写', url: '无需填写', model: 'mock' },
+};
+// 模拟引擎可注入的故障
+const MOCK_FAILURES = [
+  ['', '不注入'], ['network', '网络中断'], ['timeout', '超时'], ['rate_limit', '限流'],
+  ['server', '服务端错误'], ['auth', '鉴权失败'], ['quota', '额度不足'], ['content_filter', '内容拦截'],
+];
 
 function renderProviderList() {
   const list = document.getElementById('providerList');
@@ -75,18 +93,51 @@ function renderProviderList() {
             <button class="provider-delete-btn" data-idx="${i}">×</button>
           </div>
         </div>
+        <div class="provider-card-row">
+          <label>协议</label>
+          <select class="field-select provider-protocol" data-idx="${i}">
+            ${Object.entries(PROVIDER_PROTOCOLS).map(([key, info]) =>
+              `<option value="${key}" ${(p.protocol || 'openai') === key ? 'selected' : ''}>${info.label}</option>`).join('')}
+          </select>
+        </div>
         <div class="provider-card-row">
           <label>API Key</label>
-          <input type="password" class="field-input provider-apikey" data-idx="${i}" value="${p.api_key || ''}" placeholder="sk-...">
+          <input type="password" class="field-input provider-apikey" data-idx="${i}" value="${p.api_key || ''}" placeholder="${(PROVIDER_PROTOCOLS[p.protocol] || PROVIDER_PROTOCOLS.openai).key}">
         </div>
         <div class="provider-card-row">
           <label>Base URL</label>
-          <input type="text" class="field-input provider-baseurl" data-idx="${i}" value="${p.base_url || ''}" placeholder="https://api.deepseek.com/v1">
+          <input type="text" class="field-input provider-baseurl" data-idx="${i}" value="${p.base_url || ''}" placeholder="${(PROVIDER_PROTOCOLS[p.protocol] || PROVIDER_PROTOCOLS.openai).url}">
         </div>
         <div class="provider-card-row">
           <label>Model</label>
-          <input type="text" class="field-input provider-model" data-idx="${i}" value="${p.model || ''}" placeholder="deepseek-chat">
+          <input type="text" class="field-input provider-model" data-idx="${i}" value="${p.model || ''}" placeholder="${(PROVIDER_PROTOCOLS[p.protocol] || PROVIDER_PROTOCOLS.openai).model}">
+        </div>
+        <div class="provider-card-row" title="连接超时 / 两次收到数据的最长间隔（秒） / 失败重试次数">
+          <label>超时/重试</label>
+          <input type="number" class="field-input provider-connect-timeout" data-idx="${i}" min="1" value="${p.connect_timeout_secs ?? 10}" style="width:56px">
+          <input type="number" class="field-input provider-read-timeout" data-idx="${i}" min="1" value="${p.read_timeout_secs ?? 60}" style="width:56px">
+          <input type="number" class="field-input provider-retries" data-idx="${i}" min="0" max="10" value="${p.max_retries ?? 3}" style="width:48px">
         </div>
+        <div class="provider-card-row" title="每月 token 上限，用完后该接口暂停调用；0 表示不限">
+          <label>月度预算</label>
+          <input type="number" class="field-input provider-budget" data-idx="${i}" min="0" step="10000" value="${p.monthly_budget_tokens || 0}">
+        </div>
+        ${(p.protocol || 'openai') === 'openai' ? `
+        <div class="provider-card-row" title="流式请求附带 stream_options 以取得准确用量；接口报 400 时关闭，改为按字数估算">
+          <label>返回用量</label>
+          <input type="checkbox" class="field-input provider-stream-usage" data-idx="${i}" ${p.stream_usage !== false ? 'checked' : ''}>
+        </div>` : ''}
+        ${p.protocol === 'mock' ? `
+        <div class="provider-card-row" title="每段字数 / 分段间隔（毫秒） / 注入的故障 / 输出几段后触发">
+          <label>模拟</label>
+          <input type="number" class="field-input provider-mock-chunk" data-idx="${i}" min="1" value="${p.mock?.chunk_chars ?? 8}" style="width:48px">
+          <input type="number" class="field-input provider-mock-delay" data-idx="${i}" min="0" step="10" value="${p.mock?.delay_ms ?? 40}" style="width:56px">
+          <select class="field-input provider-mock-fail" data-idx="${i}">
+            ${MOCK_FAILURES.map(([kind, label]) =>
+              `<option value="${kind}" ${(p.mock?.fail || '') === kind ? 'selected' : ''}>${label}</option>`).join('')}
+          </select>
+          <input type="number" class="field-input provider-mock-after" data-idx="${i}" min="0" value="${p.mock?.fail_after_chunks ?? 0}" style="width:48px">
+        </div>` : ''}
       </div>
     `).join('');
 
@@ -128,6 +179,14 @@ function renderProviderList() {
         syncProviderFromUI(idx);
       });
     });
+
+    // 切换协议后重绘，更新占位提示
+    list.querySelectorAll('.provider-protocol').forEach(sel => {
+      sel.addEventListener('change', () => {
+        syncProviderFromUI(parseInt(sel.dataset.idx));
+        renderProviderList();
+      });
+    });
   }
 }
 
@@ -136,20 +195,62 @@ function syncProviderFromUI(idx) {
   if (!list || !aiProviders[idx]) return;
   const card = list.querySelector(`.provider-card[data-idx="${idx}"]`);
   if (!card) return;
+  const protocol = card.querySelector('.provider-protocol');
   const apikey = card.querySelector('.provider-apikey');
   const baseurl = card.querySelector('.provider-baseurl');
   const model = card.querySelector('.provider-model');
+  const connectTimeout = card.querySelector('.provider-connect-timeout');
+  const readTimeout = card.querySelector('.provider-read-timeout');
+  const retries = card.querySelector('.provider-retries');
+  const budget = card.querySelector('.provider-budget');
+  if (protocol) aiProviders[idx].protocol = protocol.value;
   if (apikey) aiProviders[idx].api_key = apikey.value;
   if (baseurl) aiProviders[idx].base_url = baseurl.value;
   if (model) aiProviders[idx].model = model.value;
+  if (connectTimeout) aiProviders[idx].connect_timeout_secs = Math.max(1, parseInt(connectTimeout.value) || 10);
+  if (readTimeout) aiProviders[idx].read_timeout_secs = Math.max(1, parseInt(readTimeout.value) || 60);
+  if (retries) aiProviders[idx].max_retries = Math.max(0, parseInt(retries.value) || 0);
+  if (budget) aiProviders[idx].monthly_budget_tokens = Math.max(0, parseInt(budget.value) || 0);
+  const streamUsage = card.querySelector('.provider-stream-usage');
+  if (streamUsage) aiProviders[idx].stream_usage = streamUsage.checked;
+  const mockChunk = card.querySelector('.provider-mock-chunk');
+  if (mockChunk) {
+    // 保留配置文件里手写的 responses
+    aiProviders[idx].mock = {
+      ...(aiProviders[idx].mock || {}),
+      chunk_chars: Math.max(1, parseInt(mockChunk.value) || 8),
+      delay_ms: Math.max(0, parseInt(card.querySelector('.provider-mock-delay').value) || 0),
+      fail: card.querySelector('.provider-mock-fail').value || null,
+      fail_after_chunks: Math.max(0, parseInt(card.querySelector('.provider-mock-after').value) || 0),
+    };
+  }
 }
 
 function renderProviderSelects() {
   const activeSelect = document.getElementById('activeProviderSelect');
-  const writingSelect = document.getElementById('writingProviderSelect');
   const options = aiProviders.map(p => `<option value="${p.id}">${p.name || p.id}</option>`).join('');
   if (activeSelect) { activeSelect.innerHTML = options; activeSelect.value = activeProviderId; }
-  if (writingSelect) { writingSelect.innerHTML = options; writingSelect.value = writingProviderId; }
+  const featureEl = document.getElementById('featureProviderSelects');
+  if (featureEl) {
+    // 重新渲染前保留尚未保存的选择
+    if (featureEl.children.length) featureProviders = collectFeatureProviders();
+    featureEl.innerHTML = AI_FEATURES.map(([key, label]) => `
+      <label class="field-label">${label}使用</label>
+      <select class="field-select feature-provider-select" data-feature="${key}">
+        <option value="">跟随默认</option>${options}
+      </select>`).join('');
+    featureEl.querySelectorAll('.feature-provider-select').forEach(sel => {
+      sel.value = featureProviders[sel.dataset.feature] || '';
+    });
+  }
+}
+
+function collectFeatureProviders() {
+  const result = {};
+  document.querySelectorAll('.feature-provider-select').forEach(sel => {
+    if (sel.value) result[sel.dataset.feature] = sel.value;
+  });
+  return result;
 }
 
 const addProviderBtn = document.getElementById('addProviderBtn');
@@ -159,7 +260,7 @@ if (addProviderBtn) {
     if (!name || !name.trim()) return;
     const id = 'custom-' + Date.now();
     aiProviders.push({
-      id, name: name.trim(), api_key: '', base_url: '', model: '',
+      id, name: name.trim(), protocol: 'openai', api_key: '', base_url: '', model: '',
       temperature: 0.7, enabled: true,
     });
     renderProviderList();
@@ -167,15 +268,182 @@ if (addProviderBtn) {
   });
 }
 
+// 本月各引擎、各功能的 token 用量
+async function renderAiUsage() {
+  const el = document.getElementById('aiUsageSummary');
+  if (!el) return;
+  try {
+    const month = new Date().toISOString().slice(0, 7);
+    const usage = await invoke('get_ai_usage', { range: month });
+    if (usage.total.calls === 0) {
+      el.textContent = '本月还没有调用 AI';
+      return;
+    }
+    const tokens = (t) => (t.prompt_tokens + t.completion_tokens).toLocaleString();
+    const names = Object.fromEntries(aiProviders.map(p => [p.id, p.name || p.id]));
+    const features = Object.fromEntries(AI_FEATURES);
+    const providerLines = Object.entries(usage.by_provider).map(([id, t]) => {
+      const p = aiProviders.find(x => x.id === id);
+      const budget = p && p.monthly_budget_tokens ? ` / ${p.monthly_budget_tokens.toLocaleString()}` : '';
+      return `${names[id] || id}：${tokens(t)}${budget} tokens，${t.calls} 次`;
+    });
+    const featureLines = Object.entries(usage.by_feature).map(([key, t]) =>
+      `${features[key] || key} ${tokens(t)}`);
+    el.innerHTML = [...providerLines, featureLines.join('，')]
+      .map(line => `<div>${line}</div>`).join('');
+    if (usage.total.estimated_calls > 0) {
+      el.innerHTML += `<div>其中 ${usage.total.estimated_calls} 次接口未返回用量，为估算值</div>`;
+    }
+  } catch (e) {
+    el.textContent = `读取用量失败：${e}`;
+  }
+}
+
+// === AI 生成历史 ===
+const aiHistoryFeatureSelect = document.getElementById('aiHistoryFeatureSelect');
+const aiHistoryDetail = document.getElementById('aiHistoryDetail');
+const aiHistoryCopyBtn = document.getElementById('aiHistoryCopyBtn');
+
+async function renderAiHistory() {
+  const el = document.getElementById('aiHistoryList');
+  if (!el || !aiHistoryFeatureSelect) return;
+  if (!aiHistoryFeatureSelect.options.length) {
+    aiHistoryFeatureSelect.innerHTML = '<option value="">全部功能</option>' +
+      AI_FEATURES.map(([key, label]) => `<option value="${key}">${label}</option>`).join('');
+  }
+  aiHistoryDetail.style.display = 'none';
+  aiHistoryCopyBtn.style.display = 'none';
+  try {
+    const entries = await invoke('list_ai_history', { feature: aiHistoryFeatureSelect.value || null, limit: 30 });
+    if (entries.length === 0) {
+      el.textContent = '还没有生成记录';
+      return;
+    }
+    const features = Object.fromEntries(AI_FEATURES);
+    el.innerHTML = entries.map(e => {
+      const head = e.text.split('\n').find(l => l.trim()) || '';
+      return `<div class="ai-history-item" data-feature="${e.feature}" data-id="${e.id}" style="cursor:pointer">
+        ${e.ts.replace('T', ' ')} · ${features[e.feature] || e.feature} · ${escapeHtml(e.model)}${e.incomplete ? ' · 不完整' : ''}
+        — ${escapeHtml(head.slice(0, 40))}</div>`;
+    }).join('');
+    el.querySelectorAll('.ai-history-item').forEach(item => {
+      item.addEventListener('click', async () => {
+        try {
+          const entry = await invoke('restore_ai_history', { feature: item.dataset.feature, id: item.dataset.id });
+          aiHistoryDetail.textContent = entry.text;
+          aiHistoryDetail.style.display = '';
+          aiHistoryCopyBtn.style.display = '';
+        } catch (e) {
+          settingsStatus.textContent = `读取失败：${e}`;
+        }
+      });
+    });
+  } catch (e) {
+    el.textContent = `读取生成历史失败：${e}`;
+  }
+}
+
+aiHistoryFeatureSelect?.addEventListener('change', renderAiHistory);
+
+aiHistoryCopyBtn?.addEventListener('click', async () => {
+  try {
+    await navigator.clipboard.writeText(aiHistoryDetail.textContent);
+    settingsStatus.textContent = '已复制到剪贴板';
+  } catch (e) {
+    settingsStatus.textContent = `复制失败：${e}`;
+  }
+});
+
+// === 提示词模板 ===
+let promptTemplates = [];
+const promptSelect = document.getElementById('promptSelect');
+const promptSystemInput = document.getElementById('promptSystemInput');
+const promptUserInput = document.getElementById('promptUserInput');
+const promptPreview = document.getElementById('promptPreview');
+
+async function loadPrompts() {
+  try {
+    const list = await invoke('list_prompts');
+    promptTemplates = list.prompts;
+    if (list.error) settingsStatus.textContent = `${list.error}，当前显示的是内置模板，修正文件前无法保存`;
+    const current = promptSelect.value;
+    promptSelect.innerHTML = promptTemplates.map(t =>
+      `<option value="${t.name}">${t.title}${t.customized ? '（已修改）' : ''}</option>`).join('');
+    if (current) promptSelect.value = current;
+    showPrompt();
+  } catch (e) {
+    console.error('prompts:', e);
+  }
+}
+
+function showPrompt() {
+  const t = promptTemplates.find(x => x.name === promptSelect.value) || promptTemplates[0];
+  if (!t) return;
+  promptSystemInput.value = t.system;
+  promptUserInput.value = t.user;
+  document.getElementById('promptVariables').innerHTML = '可用变量：' +
+    t.variables.map(v => `<code>{${v.name}}</code> ${v.description}`).join('；');
+  promptPreview.style.display = 'none';
+}
+
+promptSelect?.addEventListener('change', showPrompt);
+
+document.getElementById('savePromptBtn')?.addEventListener('click', async () => {
+  try {
+    await invoke('save_prompt', {
+      name: promptSelect.value, system: promptSystemInput.value, user: promptUserInput.value,
+    });
+    settingsStatus.textContent = '模板已保存';
+    await loadPrompts();
+  } catch (e) {
+    settingsStatus.textContent = `保存失败：${e}`;
+  }
+});
+
+document.getElementById('resetPromptBtn')?.addEventListener('click', async () => {
+  if (!confirm('恢复为默认模板？你的修改会丢失。')) return;
+  try {
+    await invoke('reset_prompt', { name: promptSelect.value });
+    settingsStatus.textContent = '已恢复默认模板';
+    await loadPrompts();
+  } catch (e) {
+    settingsStatus.textContent = `恢复失败：${e}`;
+  }
+});
+
+// 预览的是已保存的模板，先保存再预览
+document.getElementById('previewPromptBtn')?.addEventListener('click', async () => {
+  try {
+    const r = await invoke('preview_prompt', { name: promptSelect.value });
+    promptPreview.textContent = `【系统提示】\n${r.system}\n\n【用户消息】\n${r.user}`;
+    promptPreview.style.display = '';
+    if (r.warning) settingsStatus.textContent = `${r.warning}，预览的是内置模板`;
+  } catch (e) {
+    settingsStatus.textContent = `预览失败：${e}`;
+  }
+});
+
 // 加载配置
 async function loadSettings() {
   try {
     const cfg = await invoke('get_config');
-    apiKeyInput.value = cfg.api_key || '';
+    loadedConfig = cfg;
+    const configError = await invoke('get_config_error');
+    const startupWarnings = await invoke('get_startup_warnings');
+    if (startupWarnings.length) settingsStatus.textContent = `启动时出错：${startupWarnings.join('；')}`;
+    if (configError) settingsStatus.textContent = `${configError}，当前显示的是默认设置，修正文件前无法保存`;
+    document.getElementById('secretStoreSelect').value = cfg.secret_store || 'keyring';
     langSelect.value = cfg.language || 'bilingual';
     dataDirInput.value = cfg.data_dir || '';
     intervalInput.value = cfg.interval || 30;
     goalInput.value = cfg.daily_goal_minutes || 0;
+    document.getElementById('autoStartMonitorCheck').checked = !!cfg.auto_start_monitor;
+    document.getElementById('launchOnLoginCheck').checked = !!cfg.launch_on_login;
+    const privacy = cfg.privacy || {};
+    document.getElementById('privacyExesInput').value = (privacy.exclude_exes || []).join(', ');
+    document.getElementById('privacyPatternsInput').value = (privacy.exclude_title_patterns || []).join('\n');
+    document.getElementById('privacyModeSelect').value = privacy.mode || 'private';
+    document.getElementById('privacyIncognitoCheck').checked = privacy.detect_incognito !== false;
     // 加载性格
     selectedPersonality = cfg.personality || 'gentle';
     personalityCards.forEach(c => {
@@ -207,9 +475,13 @@ async function loadSettings() {
     // AI 引擎
     aiProviders = cfg.ai_providers || [];
     activeProviderId = cfg.active_provider || '';
-    writingProviderId = cfg.writing_provider || '';
+    featureProviders = cfg.feature_providers || {};
     renderProviderList();
     renderProviderSelects();
+    renderAiUsage();
+    renderAiHistory();
+    document.getElementById('aiCacheHoursInput').value = cfg.ai_cache_hours || 0;
+    loadPrompts();
   } catch (e) {
     console.error('load config:', e);
   }
@@ -225,6 +497,69 @@ browseDirBtn.addEventListener('click', async () => {
   }
 });
 
+// 解锁加密密钥文件（没有系统钥匙串时使用）
+document.getElementById('unlockSecretsBtn').addEventListener('click', async () => {
+  const input = document.getElementById('secretPassphraseInput');
+  try {
+    await invoke('unlock_secrets', { passphrase: input.value });
+    input.value = '';
+    settingsStatus.textContent = '密钥文件已解锁 ✓';
+  } catch (e) {
+    settingsStatus.textContent = '解锁失败: ' + e;
+  }
+});
+
+// 迁移数据目录：复制并校验全部数据，成功后才切换配置
+const PHASE_LABELS = { copy: '复制', verify: '校验', cleanup: '清理旧目录' };
+// config.json 被改坏时保留当前设置，提示用户修正
+listen('config-error', (event) => {
+  settingsStatus.textContent = `${event.payload}，已保留当前设置`;
+});
+
+listen('data-dir-progress', (event) => {
+  const p = event.payload;
+  settingsStatus.textContent = `迁移中：${PHASE_LABELS[p.phase] || p.phase} ${p.done}/${p.total}`;
+});
+
+document.getElementById('moveDataDirBtn').addEventListener('click', async () => {
+  const newPath = dataDirInput.value.trim();
+  if (!newPath || newPath === (loadedConfig && loadedConfig.data_dir)) {
+    settingsStatus.textContent = '请先选择新的数据目录';
+    return;
+  }
+  try {
+    const r = await invoke('move_data_dir', { newPath });
+    settingsStatus.textContent = r.left_behind > 0
+      ? `已迁移 ${r.files} 个文件，旧目录留下 ${r.left_behind} 个文件（迁移期间有改动或未能删除，请手动核对）：${r.left_files.slice(0, 5).join('、')}${r.left_behind > 5 ? ' 等' : ''}`
+      : `已迁移 ${r.files} 个文件 ✓`;
+  } catch (e) {
+    settingsStatus.textContent = '迁移失败: ' + e;
+  }
+});
+
+// 活动数据导入导出
+async function exportActivity(format) {
+  const to = new Date();
+  const from = new Date(to.getFullYear() - 1, to.getMonth(), to.getDate() + 1);
+  const fmt = d => `${d.getFullYear()}-${String(d.getMonth() + 1).padStart(2, '0')}-${String(d.getDate()).padStart(2, '0')}`;
+  try {
+    const count = await invoke('export_activity', { from: fmt(from), to: fmt(to), format });
+    if (count !== null) settingsStatus.textContent = `已导出 ${count} 条记录 ✓`;
+  } catch (e) {
+    settingsStatus.textContent = '导出失败: ' + e;
+  }
+}
+document.getElementById('exportCsvBtn').addEventListener('click', () => exportActivity('csv'));
+document.getElementById('exportJsonBtn').addEventListener('click', () => exportActivity('json'));
+document.getElementById('importActivityBtn').addEventListener('click', async () => {
+  try {
+    const r = await invoke('import_activity', { source: null });
+    if (r) settingsStatus.textContent = `导入 ${r.imported} 条（${r.days} 天），跳过重复 ${r.skipped} 条 ✓`;
+  } catch (e) {
+    settingsStatus.textContent = '导入失败: ' + e;
+  }
+});
+
 // 保存设置
 saveSettingsBtn.addEventListener('click', async () => {
   // 同步所有 provider 输入
@@ -243,19 +578,28 @@ saveSettingsBtn.addEventListener('click', async () => {
   }
 
   const activeSelect = document.getElementById('activeProviderSelect');
-  const writingSelect = document.getElementById('writingProviderSelect');
 
   const cfg = {
-    api_key: apiKeyInput.value.trim(),
+    ...loadedConfig,
+    secret_store: document.getElementById('secretStoreSelect').value,
     language: langSelect.value,
     data_dir: dataDirInput.value.trim(),
     interval: Math.max(5, parseInt(intervalInput.value) || 30),
     daily_goal_minutes: Math.max(0, parseInt(goalInput.value) || 0),
+    auto_start_monitor: document.getElementById('autoStartMonitorCheck').checked,
+    launch_on_login: document.getElementById('launchOnLoginCheck').checked,
+    privacy: {
+      exclude_exes: document.getElementById('privacyExesInput').value.split(',').map(s => s.trim()).filter(Boolean),
+      exclude_title_patterns: document.getElementById('privacyPatternsInput').value.split('\n').map(s => s.trim()).filter(Boolean),
+      detect_incognito: document.getElementById('privacyIncognitoCheck').checked,
+      mode: document.getElementById('privacyModeSelect').value,
+    },
     personality: selectedPersonality,
     bird_size: selectedBirdSize,
     ai_providers: aiProviders,
     active_provider: activeSelect ? activeSelect.value : '',
-    writing_provider: writingSelect ? writingSelect.value : '',
+    feature_providers: collectFeatureProviders(),
+    ai_cache_hours: Math.max(0, parseInt(document.getElementById('aiCacheHoursInput').value) || 0),
   };
   if (customPersonality) cfg.custom_personality = customPersonality;
 
@@ -266,7 +610,7 @@ saveSettingsBtn.addEventListener('click', async () => {
     currentPersonality = selectedPersonality;
     if (typeof updateChickMood === 'function') updateChickMood();
   } catch (e) {
-    settingsStatus.textContent = '保存失败';
+    settingsStatus.textContent = '保存失败: ' + e;
     console.error('save config:', e);
   }
 });
@@ -291,6 +635,8 @@ themeToggle.addEventListener('change', () => {
 
 // 初始化
 loadSettings();
+// 保存或手动修改 config.json 后同步表单
+listen('config-changed', () => loadSettings());
 
 // === 梗库弹窗 ===
 const memesOverlay = document.getElementById('memesOverlay');
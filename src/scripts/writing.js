@@ -1451,19 +1451,35 @@
       const chatSend = document.getElementById('birdChatSend');
       const chatInput = document.getElementById('birdChatInput');
       if (chatSend && chatInput) {
+        // 等待回复期间发送按钮变为「停止」
+        let chatRequest = null;
         const sendMsg = async () => {
+          if (chatRequest) {
+            cancelAiRequest(chatRequest);
+            return;
+          }
           const text = chatInput.value.trim();
           if (!text) return;
           chatInput.value = '';
           this.addChatMsg(text, 'user');
+          const requestId = newAiRequestId();
+          chatRequest = requestId;
+          chatSend.textContent = '停止';
+          let cancelled = false;
+          const unlistenError = await listenAi(requestId, 'ai-error', (p) => { cancelled = p.cancelled; });
           try {
             const result = await invoke('ai_bird_comment', {
               text: text,
               prompt: '你是一只小蓝鸟写作伙伴。用户在和你聊剧情，用简短的话回应（不超过50字）。性格：' + this.personality,
+              request_id: requestId,
             });
             this.addChatMsg(result, 'bird');
           } catch(e) {
-            this.addChatMsg('（连接失败了...）', 'bird');
+            this.addChatMsg(cancelled ? '（好吧，不说了）' : '（连接失败了...）', 'bird');
+          } finally {
+            unlistenError();
+            chatRequest = null;
+            chatSend.textContent = '发送';
           }
         };
         chatSend.addEventListener('click', sendMsg);
@@ -1554,8 +1570,11 @@
     }
   }
 
+  // 进行中的自动评价请求；上一条还没回来时不再发新的
+  let aiCommentRequest = null;
+
   async function triggerAiComment() {
-    if (!writingBird) return;
+    if (!writingBird || aiCommentRequest) return;
     const editor = document.getElementById('writingEditor');
     if (!editor) return;
     const text = editor.value;
@@ -1578,10 +1597,13 @@
       }
     }
 
+    const requestId = newAiRequestId();
+    aiCommentRequest = requestId;
     try {
       const result = await invoke('ai_bird_comment', {
         text: recentText,
         prompt: prompt,
+        request_id: requestId,
       });
       if (result && writingBird) {
         writingBird.speak('aiComment', result);
@@ -1589,34 +1611,51 @@
     } catch(e) {
       // AI 不可用时静默
       console.log('AI 评价失败:', e);
+    } finally {
+      aiCommentRequest = null;
     }
   }
 
   // ============================================
   // AI 工具栏按钮
   // ============================================
+  // 进行中的工具栏 AI 请求，按钮在等待期间变为「停止」
+  let writingAiRequest = null;
+
   function setupAiToolbarButtons() {
     const suggestBtn = document.getElementById('writingAiSuggestBtn');
     const checkBtn = document.getElementById('writingAiCheckBtn');
 
     if (suggestBtn) {
       suggestBtn.addEventListener('click', async () => {
+        if (writingAiRequest) {
+          cancelAiRequest(writingAiRequest);
+          return;
+        }
         const editor = document.getElementById('writingEditor');
         if (!editor || !editor.value.trim()) return;
         const lastParagraph = editor.value.split('\n').filter(Boolean).slice(-3).join('\n');
-        suggestBtn.disabled = true;
-        suggestBtn.querySelector('span:last-child').textContent = '思考中...';
+        const requestId = newAiRequestId();
+        writingAiRequest = requestId;
+        if (checkBtn) checkBtn.disabled = true;
+        suggestBtn.querySelector('span:last-child').textContent = '停止';
+        // 用户点了停止就不再弹失败提示
+        let cancelled = false;
+        const unlistenError = await listenAi(requestId, 'ai-error', (p) => { cancelled = p.cancelled; });
         try {
           const result = await invoke('ai_writing_suggest', {
             book_id: 'default',
             chapter_id: currentChapterId || '',
             context: lastParagraph,
+            request_id: requestId,
           });
           showAiSuggestResult(result);
         } catch(e) {
-          alert('AI 建议失败: ' + e);
+          if (!cancelled) alert('AI 建议失败: ' + e);
         } finally {
-          suggestBtn.disabled = false;
+          unlistenError();
+          writingAiRequest = null;
+          if (checkBtn) checkBtn.disabled = false;
           suggestBtn.querySelector('span:last-child').textContent = 'AI建议';
         }
       });
@@ -1624,20 +1663,32 @@
 
     if (checkBtn) {
       checkBtn.addEventListener('click', async () => {
+        if (writingAiRequest) {
+          cancelAiRequest(writingAiRequest);
+          return;
+        }
         const editor = document.getElementById('writingEditor');
         if (!editor || !editor.value.trim()) return;
-        checkBtn.disabled = true;
-        checkBtn.querySelector('span:last-child').textContent = '检查中...';
+        const requestId = newAiRequestId();
+        writingAiRequest = requestId;
+        if (suggestBtn) suggestBtn.disabled = true;
+        checkBtn.querySelector('span:last-child').textContent = '停止';
+        // 用户点了停止就不再弹失败提示
+        let cancelled = false;
+        const unlistenError = await listenAi(requestId, 'ai-error', (p) => { cancelled = p.cancelled; });
         try {
           const result = await invoke('ai_consistency_check', {
             book_id: 'default',
             chapter_id: currentChapterId || '',
+            request_id: requestId,
           });
           showAiSuggestResult(result, '一致性检查结果');
         } catch(e) {
-          alert('一致性检查失败: ' + e);
+          if (!cancelled) alert('一致性检查失败: ' + e);
         } finally {
-          checkBtn.disabled = false;
+          unlistenError();
+          writingAiRequest = null;
+          if (suggestBtn) suggestBtn.disabled = false;
           checkBtn.querySelector('span:last-child').textContent = '检查';
         }
       });